@@ -0,0 +1,4 @@
+ALTER TABLE registrations
+    DROP INDEX idx_confirmation_token,
+    DROP COLUMN confirmation_expires_at,
+    DROP COLUMN confirmation_token;
@@ -0,0 +1,5 @@
+-- One-time tokens for guardian email confirmation (double opt-in)
+ALTER TABLE registrations
+    ADD COLUMN confirmation_token VARCHAR(64) NULL,
+    ADD COLUMN confirmation_expires_at TIMESTAMP NULL,
+    ADD UNIQUE INDEX idx_confirmation_token (confirmation_token);
@@ -0,0 +1,3 @@
+ALTER TABLE registrations
+    DROP INDEX idx_manage_token,
+    DROP COLUMN manage_token;
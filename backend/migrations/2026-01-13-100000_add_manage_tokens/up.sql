@@ -0,0 +1,4 @@
+-- Secret token letting guardians view, cancel or reschedule their registration
+ALTER TABLE registrations
+    ADD COLUMN manage_token VARCHAR(64) NULL,
+    ADD UNIQUE INDEX idx_manage_token (manage_token);
@@ -0,0 +1,3 @@
+ALTER TABLE registrations
+    DROP INDEX idx_waitlist,
+    DROP COLUMN waitlisted;
@@ -0,0 +1,4 @@
+-- Waitlist for full sessions, the queue order is the registration id
+ALTER TABLE registrations
+    ADD COLUMN waitlisted BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD INDEX idx_waitlist (session_id, waitlisted, id);
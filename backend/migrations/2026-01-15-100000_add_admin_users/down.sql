@@ -0,0 +1,5 @@
+ALTER TABLE admin_sessions
+    DROP FOREIGN KEY fk_admin_sessions_user,
+    DROP COLUMN user_id;
+
+DROP TABLE IF EXISTS admin_users;
@@ -0,0 +1,16 @@
+-- Named admin accounts with roles
+CREATE TABLE admin_users (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    username VARCHAR(50) NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE INDEX idx_username (username)
+);
+
+-- Existing sessions cannot be attributed to a user, log everybody out
+DELETE FROM admin_sessions;
+
+ALTER TABLE admin_sessions
+    ADD COLUMN user_id INTEGER NOT NULL,
+    ADD CONSTRAINT fk_admin_sessions_user FOREIGN KEY (user_id) REFERENCES admin_users(id) ON DELETE CASCADE;
@@ -0,0 +1,23 @@
+-- Append-only log of admin actions
+CREATE TABLE audit_log (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    actor_id INTEGER NULL,
+    actor VARCHAR(50) NULL,
+    ip_address VARCHAR(45) NULL,
+    action VARCHAR(50) NOT NULL,
+    target_id VARCHAR(64) NULL,
+    before_data TEXT NULL,
+    after_data TEXT NULL,
+    INDEX idx_audit_created_at (created_at),
+    INDEX idx_audit_action (action),
+    INDEX idx_audit_actor (actor)
+);
+
+-- Entries must never change once written. With binary logging enabled, creating
+-- triggers needs SUPER or log_bin_trust_function_creators = 1, see Config.toml.example.
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_log is append-only';
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_log is append-only';
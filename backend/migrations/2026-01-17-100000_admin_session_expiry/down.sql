@@ -0,0 +1,3 @@
+ALTER TABLE admin_sessions
+    DROP COLUMN last_seen_at,
+    DROP COLUMN id;
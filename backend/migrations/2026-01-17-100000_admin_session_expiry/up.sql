@@ -0,0 +1,7 @@
+-- Sessions created before expiry was enforced never time out, log everybody out
+DELETE FROM admin_sessions;
+
+-- Surrogate id so sessions can be listed and revoked without exposing tokens
+ALTER TABLE admin_sessions
+    ADD COLUMN id INTEGER NOT NULL AUTO_INCREMENT UNIQUE,
+    ADD COLUMN last_seen_at TIMESTAMP NULL;
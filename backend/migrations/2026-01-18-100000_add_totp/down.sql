@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS admin_recovery_codes;
+
+ALTER TABLE admin_sessions
+    DROP COLUMN pending_totp;
+
+ALTER TABLE admin_users
+    DROP COLUMN totp_last_step,
+    DROP COLUMN totp_enabled,
+    DROP COLUMN totp_secret;
@@ -0,0 +1,19 @@
+-- Optional TOTP second factor for admin accounts
+ALTER TABLE admin_users
+    ADD COLUMN totp_secret VARCHAR(64) NULL,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_step BIGINT NULL;
+
+-- Sessions that passed the password check but still await the TOTP code
+ALTER TABLE admin_sessions
+    ADD COLUMN pending_totp BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Single-use recovery codes, stored as SHA-256 hashes
+CREATE TABLE admin_recovery_codes (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_user_code (user_id, code_hash),
+    CONSTRAINT fk_recovery_codes_user FOREIGN KEY (user_id) REFERENCES admin_users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+-- Scheduled registration windows. A window without turnus and session applies
+-- to every session, a turnus or session window overrides it for its sessions.
+CREATE TABLE registration_windows (
+    id INTEGER AUTO_INCREMENT PRIMARY KEY,
+    turnus INTEGER NULL,
+    session_id INTEGER NULL,
+    opens_at DATETIME NULL,
+    closes_at DATETIME NULL,
+    -- Bookings close this many hours before the session starts
+    closes_hours_before INTEGER NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_registration_windows_session FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
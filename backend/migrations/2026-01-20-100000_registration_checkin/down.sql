@@ -0,0 +1,4 @@
+ALTER TABLE registrations
+    DROP INDEX idx_checkin_code,
+    DROP COLUMN checked_in_at,
+    DROP COLUMN checkin_code;
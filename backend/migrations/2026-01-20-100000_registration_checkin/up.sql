@@ -0,0 +1,8 @@
+-- Code in the guardian's QR image, scanned at the entrance to record attendance
+ALTER TABLE registrations
+    ADD COLUMN checkin_code VARCHAR(64) NULL,
+    ADD COLUMN checked_in_at TIMESTAMP NULL,
+    ADD UNIQUE INDEX idx_checkin_code (checkin_code);
+
+-- Existing registrations get a code as well
+UPDATE registrations SET checkin_code = REPLACE(UUID(), '-', '') WHERE checkin_code IS NULL;
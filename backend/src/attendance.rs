@@ -0,0 +1,269 @@
+// Printable attendance sheets, one page per session with its confirmed students
+
+use chrono::Datelike;
+use printpdf::path::PaintMode;
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point, Rect,
+};
+use std::borrow::Cow;
+
+use crate::models::{Registration, Session};
+
+// A4 portrait, all measures in millimetres from the bottom left corner
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 9.0;
+const TABLE_TOP: f32 = 252.0;
+
+// Column titles and widths, together they span the page between the margins
+const COLUMNS: [(&str, f32); 5] = [
+    ("#", 10.0),
+    ("Žiak", 62.0),
+    ("Telefón zák. zástupcu", 43.0),
+    ("Prítomný", 22.0),
+    ("Podpis", 43.0),
+];
+
+// Longest student name that still fits its column
+const NAME_MAX_CHARS: usize = 34;
+
+const WEEKDAYS: [&str; 7] = [
+    "pondelok", "utorok", "streda", "štvrtok", "piatok", "sobota", "nedeľa",
+];
+
+/// A session and its confirmed registrations, in print order
+pub struct SessionSheet {
+    pub session: Session,
+    pub students: Vec<Registration>,
+}
+
+/// Font of the sheets
+pub enum SheetFont {
+    /// Contents of a TrueType file, prints any character it has glyphs for
+    External(Vec<u8>),
+    /// Helvetica, needs no file but only covers Windows-1252
+    Builtin,
+}
+
+/// Writes text with the document font
+struct Pen {
+    font: IndirectFontRef,
+    builtin: bool,
+}
+
+impl Pen {
+    fn text(&self, layer: &PdfLayerReference, text: &str, size: f32, x: f32, y: f32) {
+        let text = if self.builtin {
+            fold_to_windows_1252(text)
+        } else {
+            Cow::Borrowed(text)
+        };
+        layer.use_text(text, size, Mm(x), Mm(y), &self.font);
+    }
+}
+
+/// Replace Slovak and Czech letters missing from Windows-1252 with their base letter,
+/// the built-in fonts would silently drop them
+fn fold_to_windows_1252(text: &str) -> Cow<'_, str> {
+    let fold = |c: char| match c {
+        'č' => 'c',
+        'Č' => 'C',
+        'ď' => 'd',
+        'Ď' => 'D',
+        'ĺ' | 'ľ' => 'l',
+        'Ĺ' | 'Ľ' => 'L',
+        'ň' => 'n',
+        'Ň' => 'N',
+        'ŕ' | 'ř' => 'r',
+        'Ŕ' | 'Ř' => 'R',
+        'ť' => 't',
+        'Ť' => 'T',
+        'ě' => 'e',
+        'Ě' => 'E',
+        'ů' => 'u',
+        'Ů' => 'U',
+        c => c,
+    };
+    if text.chars().all(|c| fold(c) == c) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.chars().map(fold).collect())
+    }
+}
+
+fn line(layer: &PdfLayerReference, from: (f32, f32), to: (f32, f32)) {
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(from.0), Mm(from.1)), false),
+            (Point::new(Mm(to.0), Mm(to.1)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max_chars - 3).collect();
+        format!("{}...", cut)
+    }
+}
+
+/// Session details above the table
+fn draw_heading(layer: &PdfLayerReference, pen: &Pen, sheet: &SessionSheet, continued: bool) {
+    let session = &sheet.session;
+    let mut title = format!("{} {}", session.field_code, session.field_name);
+    if continued {
+        title.push_str(" (pokračovanie)");
+    }
+    pen.text(layer, &title, 16.0, MARGIN, PAGE_HEIGHT - MARGIN - 6.0);
+
+    let weekday = WEEKDAYS[session.session_date.weekday().num_days_from_monday() as usize];
+    let when = format!(
+        "Dátum: {} ({})    Čas: {} - {}    Turnus: {}",
+        session.session_date.format("%d.%m.%Y"),
+        weekday,
+        session.start_time.format("%H:%M"),
+        session.end_time.format("%H:%M"),
+        session.turnus,
+    );
+    pen.text(layer, &when, 11.0, MARGIN, PAGE_HEIGHT - MARGIN - 14.0);
+
+    let count = format!(
+        "Prihlásení: {} / {}",
+        sheet.students.len(),
+        session.max_capacity
+    );
+    pen.text(layer, &count, 11.0, MARGIN, PAGE_HEIGHT - MARGIN - 21.0);
+}
+
+/// Horizontal rule below a table row and the column borders beside it
+fn draw_row_borders(layer: &PdfLayerReference, top: f32) {
+    let bottom = top - ROW_HEIGHT;
+    line(layer, (MARGIN, bottom), (PAGE_WIDTH - MARGIN, bottom));
+
+    let mut x = MARGIN;
+    line(layer, (x, top), (x, bottom));
+    for (_, width) in COLUMNS {
+        x += width;
+        line(layer, (x, top), (x, bottom));
+    }
+}
+
+fn draw_table_header(layer: &PdfLayerReference, pen: &Pen) {
+    line(layer, (MARGIN, TABLE_TOP), (PAGE_WIDTH - MARGIN, TABLE_TOP));
+    draw_row_borders(layer, TABLE_TOP);
+
+    let mut x = MARGIN;
+    for (title, width) in COLUMNS {
+        pen.text(layer, title, 9.0, x + 1.5, TABLE_TOP - 6.0);
+        x += width;
+    }
+}
+
+fn draw_student_row(
+    layer: &PdfLayerReference,
+    pen: &Pen,
+    top: f32,
+    number: usize,
+    student: &Registration,
+) {
+    draw_row_borders(layer, top);
+
+    let baseline = top - 6.0;
+    let name = format!(
+        "{} {}",
+        student.student_last_name, student.student_first_name
+    );
+    let mut x = MARGIN;
+    let cells = [
+        format!("{}.", number),
+        truncate(&name, NAME_MAX_CHARS),
+        student.guardian_phone.clone(),
+    ];
+    for (text, (_, width)) in cells.iter().zip(COLUMNS) {
+        pen.text(layer, text, 10.0, x + 1.5, baseline);
+        x += width;
+    }
+
+    // Box to tick in the attendance column
+    let box_left = x + (COLUMNS[3].1 - 4.5) / 2.0;
+    let box_bottom = top - (ROW_HEIGHT + 4.5) / 2.0;
+    layer.add_rect(
+        Rect::new(
+            Mm(box_left),
+            Mm(box_bottom),
+            Mm(box_left + 4.5),
+            Mm(box_bottom + 4.5),
+        )
+        .with_mode(PaintMode::Stroke),
+    );
+}
+
+/// Render the sheets into a PDF, long lists continue on further pages
+pub fn render(sheets: &[SessionSheet], font: SheetFont) -> Result<Vec<u8>, printpdf::Error> {
+    let (doc, first_page, first_layer) = PdfDocument::new(
+        "Prezenčné listiny",
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Listina",
+    );
+
+    let pen = match font {
+        SheetFont::External(bytes) => Pen {
+            font: doc.add_external_font(bytes.as_slice())?,
+            builtin: false,
+        },
+        SheetFont::Builtin => Pen {
+            font: doc.add_builtin_font(BuiltinFont::Helvetica)?,
+            builtin: true,
+        },
+    };
+
+    let mut unused_page = Some((first_page, first_layer));
+    let mut new_page = || {
+        let (page, layer) = unused_page
+            .take()
+            .unwrap_or_else(|| doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Listina"));
+        let layer = doc.get_page(page).get_layer(layer);
+        layer.set_outline_thickness(0.5);
+        layer
+    };
+
+    for sheet in sheets {
+        let mut students = sheet.students.iter().enumerate().peekable();
+        let mut continued = false;
+
+        loop {
+            let layer = new_page();
+            draw_heading(&layer, &pen, sheet, continued);
+
+            if sheet.students.is_empty() {
+                pen.text(
+                    &layer,
+                    "Na termín nie je prihlásený žiadny žiak.",
+                    11.0,
+                    MARGIN,
+                    TABLE_TOP - 6.0,
+                );
+                break;
+            }
+
+            draw_table_header(&layer, &pen);
+            let mut top = TABLE_TOP - ROW_HEIGHT;
+            while let Some((i, student)) = students.next_if(|_| top - ROW_HEIGHT >= MARGIN) {
+                draw_student_row(&layer, &pen, top, i + 1, student);
+                top -= ROW_HEIGHT;
+            }
+
+            if students.peek().is_none() {
+                break;
+            }
+            continued = true;
+        }
+    }
+
+    doc.save_to_bytes()
+}
@@ -0,0 +1,51 @@
+// Append-only audit log of admin actions
+
+use rocket::serde::Serialize;
+use rocket::serde::json::serde_json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use std::net::IpAddr;
+
+use crate::models::{AdminIdentity, NewAuditLogRow};
+use crate::schema::audit_log;
+
+/// One admin action to be recorded
+#[derive(Default)]
+pub struct AuditEntry<'a> {
+    pub action: &'a str,
+    /// Logged in admin, `None` for anonymous actions such as a failed login
+    pub actor: Option<&'a AdminIdentity>,
+    pub ip: Option<IpAddr>,
+    pub target_id: Option<String>,
+    /// JSON snapshot of the target before the action
+    pub before: Option<String>,
+    /// JSON snapshot of the target after the action
+    pub after: Option<String>,
+}
+
+/// Serialize a value for the before/after columns
+pub fn snapshot<T: Serialize>(value: &T) -> Option<String> {
+    serde_json::to_string(value).ok()
+}
+
+/// Append an entry to the audit log. Failures are logged, the action itself
+/// has already happened at this point.
+pub async fn record(db: &mut AsyncMysqlConnection, entry: AuditEntry<'_>) {
+    let row = NewAuditLogRow {
+        actor_id: entry.actor.map(|actor| actor.user_id),
+        actor: entry.actor.map(|actor| actor.username.clone()),
+        ip_address: entry.ip.map(|ip| ip.to_string()),
+        action: entry.action.to_string(),
+        target_id: entry.target_id,
+        before_data: entry.before,
+        after_data: entry.after,
+    };
+
+    if let Err(e) = diesel::insert_into(audit_log::table)
+        .values(&row)
+        .execute(db)
+        .await
+    {
+        eprintln!("Error writing audit log entry {}: {}", row.action, e);
+    }
+}
@@ -0,0 +1,240 @@
+// Admin authentication request guards
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+use rocket::outcome::{Outcome, try_outcome};
+use rocket::request::{self, FromRequest, Request};
+use rocket_db_pools::Database;
+use rocket_db_pools::diesel::{AsyncMysqlConnection, prelude::*};
+
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::models::{AdminIdentity, AdminRole, AdminUser};
+use crate::schema::{admin_sessions, admin_users};
+
+// Admin sessions are only written back when last use is older than this
+const SESSION_TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// How recently a session must have been used to stay valid
+pub fn idle_cutoff(config: &AppConfig, now: NaiveDateTime) -> NaiveDateTime {
+    now - chrono::Duration::minutes(config.admin_session_idle_minutes)
+}
+
+/// Look up the admin owning a session token, renewing the session's idle timeout
+pub async fn authenticated_admin(
+    db: &mut AsyncMysqlConnection,
+    config: &AppConfig,
+    token: &str,
+) -> Option<AdminIdentity> {
+    let now = chrono::Local::now().naive_local();
+
+    let (user, last_seen_at) = admin_sessions::table
+        .inner_join(admin_users::table)
+        .filter(admin_sessions::session_token.eq(token))
+        .filter(admin_sessions::pending_totp.eq(false))
+        .filter(admin_sessions::expires_at.gt(now))
+        .filter(admin_sessions::last_seen_at.gt(idle_cutoff(config, now)))
+        .select((AdminUser::as_select(), admin_sessions::last_seen_at))
+        .first::<(AdminUser, Option<NaiveDateTime>)>(db)
+        .await
+        .ok()?;
+
+    // Sliding renewal of the idle timeout
+    let stale = last_seen_at
+        .is_none_or(|seen| now - seen > chrono::Duration::seconds(SESSION_TOUCH_INTERVAL_SECS));
+    if stale {
+        diesel::update(admin_sessions::table.find(token))
+            .set(admin_sessions::last_seen_at.eq(now))
+            .execute(db)
+            .await
+            .map_err(|e| eprintln!("Error renewing admin session: {}", e))
+            .ok();
+    }
+
+    AdminIdentity::from_user(&user)
+}
+
+// Outcome of authenticating a request, cached so stacked guards look it up once
+struct Authentication(Result<AdminIdentity, Status>);
+
+// Helper function to find the admin owning the request's auth cookie. The pooled
+// connection is returned before the handler runs, so a request holds one at a time.
+async fn authenticate(req: &Request<'_>) -> Result<AdminIdentity, Status> {
+    let token = req
+        .cookies()
+        .get("admin_auth")
+        .map(|c| c.value().to_string())
+        .ok_or(Status::Unauthorized)?;
+
+    let config = req
+        .rocket()
+        .state::<AppConfig>()
+        .ok_or(Status::InternalServerError)?;
+    let pool = RegistrationDB::fetch(req.rocket()).ok_or(Status::InternalServerError)?;
+
+    let mut db = pool.get().await.map_err(|e| {
+        eprintln!("Error getting a connection for admin authentication: {}", e);
+        Status::ServiceUnavailable
+    })?;
+
+    authenticated_admin(&mut db, config, &token)
+        .await
+        .ok_or(Status::Unauthorized)
+}
+
+/// Any logged in admin. Fails with 401 when the auth cookie is missing or expired.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminIdentity {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let authentication = req
+            .local_cache_async(async { Authentication(authenticate(req).await) })
+            .await;
+
+        match &authentication.0 {
+            Ok(admin) => Outcome::Success(admin.clone()),
+            Err(status) => Outcome::Error((*status, ())),
+        }
+    }
+}
+
+/// A logged in admin with the manager role. Fails with 403 for viewers.
+pub struct Manager(pub AdminIdentity);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Manager {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let admin = try_outcome!(req.guard::<AdminIdentity>().await);
+
+        if admin.role < AdminRole::Manager {
+            return Outcome::Error((Status::Forbidden, ()));
+        }
+
+        Outcome::Success(Manager(admin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Cookie, Method, Status};
+    use rocket::local::asynchronous::{Client, LocalResponse};
+    use rocket::serde::json::Value;
+
+    use crate::config::AppConfig;
+    use crate::models::AdminRole;
+    use crate::test_db;
+
+    // Admin routes that work without a login
+    const PUBLIC_ADMIN_ROUTES: [&str; 4] = [
+        "/api/admin/login",
+        "/api/admin/login/totp",
+        "/api/admin/logout",
+        "/api/admin/check",
+    ];
+
+    // Routes guarded by `Manager`, dynamic segments filled with 1
+    const MANAGER_ROUTES: [(Method, &str); 18] = [
+        (Method::Post, "/api/admin/toggle"),
+        (Method::Post, "/api/admin/registrations/1/confirm"),
+        (Method::Delete, "/api/admin/registrations/1"),
+        (Method::Get, "/api/admin/audit"),
+        (Method::Post, "/api/admin/checkin"),
+        (Method::Get, "/api/admin/logins"),
+        (Method::Delete, "/api/admin/logins/1"),
+        (Method::Post, "/api/admin/sessions"),
+        (Method::Put, "/api/admin/sessions/1"),
+        (Method::Delete, "/api/admin/sessions/1"),
+        (Method::Post, "/api/admin/sessions/import"),
+        (Method::Delete, "/api/admin/users/1/totp"),
+        (Method::Get, "/api/admin/users"),
+        (Method::Post, "/api/admin/users"),
+        (Method::Delete, "/api/admin/users/1"),
+        (Method::Post, "/api/admin/windows"),
+        (Method::Put, "/api/admin/windows/1"),
+        (Method::Delete, "/api/admin/windows/1"),
+    ];
+
+    async fn client() -> Client {
+        // Loads .env so the configuration sees DATABASE_URL
+        test_db::database_url();
+        let mut config = AppConfig::load();
+        config.static_dir = std::env::temp_dir().display().to_string();
+        Client::untracked(crate::build_rocket(config))
+            .await
+            .expect("Failed to build the test server")
+    }
+
+    // Helper function to send a request a route accepts, apart from its guards
+    async fn request<'c>(
+        client: &'c Client,
+        method: Method,
+        path: String,
+        cookie: Option<&str>,
+    ) -> LocalResponse<'c> {
+        let mut request = client.req(method, path).header(ContentType::JSON);
+        if let Some(token) = cookie {
+            request = request.cookie(Cookie::new("admin_auth", token.to_string()));
+        }
+        request.dispatch().await
+    }
+
+    async fn assert_api_error(response: LocalResponse<'_>, status: Status, code: &str) {
+        assert_eq!(response.status(), status);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let body: Value = response.into_json().await.expect("Error body is not JSON");
+        assert_eq!(body["code"], code, "body: {}", body);
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn admin_routes_require_a_login() {
+        let client = client().await;
+
+        let routes: Vec<(Method, String)> = client
+            .rocket()
+            .routes()
+            .filter(|route| route.uri.path().starts_with("/api/admin"))
+            .filter(|route| !PUBLIC_ADMIN_ROUTES.contains(&route.uri.path()))
+            .map(|route| {
+                let path = route
+                    .uri
+                    .path()
+                    .split('/')
+                    .map(|segment| {
+                        if segment.starts_with('<') {
+                            "1"
+                        } else {
+                            segment
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (route.method, path)
+            })
+            .collect();
+        assert!(!routes.is_empty());
+
+        for (method, path) in routes {
+            let response = request(&client, method, path, None).await;
+            assert_api_error(response, Status::Unauthorized, "unauthorized").await;
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn viewers_cannot_use_manager_routes() {
+        let client = client().await;
+        let mut conn = test_db::connect().await;
+        let (user_id, token) = test_db::insert_admin(&mut conn, AdminRole::Viewer).await;
+
+        for (method, path) in MANAGER_ROUTES {
+            let response = request(&client, method, path.to_string(), Some(&token)).await;
+            assert_api_error(response, Status::Forbidden, "forbidden").await;
+        }
+
+        test_db::delete_admin(&mut conn, user_id).await;
+    }
+}
@@ -0,0 +1,186 @@
+// Sessions with their occupied seat counts, briefly cached because the public
+// listing is polled hard while registration is open
+
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::booking;
+use crate::models::Session;
+use crate::schema::{registrations, sessions};
+
+/// A session and the number of its occupied seats
+pub type SessionSeats = (Session, i64);
+
+/// Every session with its occupied seats counted in a single grouped query
+pub async fn load(
+    conn: &mut AsyncMysqlConnection,
+) -> Result<Vec<SessionSeats>, diesel::result::Error> {
+    sessions::table
+        .left_join(
+            registrations::table.on(registrations::session_id
+                .eq(sessions::id)
+                .and(booking::occupies_seat())),
+        )
+        .group_by(sessions::id)
+        .select((
+            Session::as_select(),
+            diesel::dsl::count(registrations::id.nullable()),
+        ))
+        .load(conn)
+        .await
+}
+
+struct CacheEntry {
+    generation: u64,
+    loaded_at: Instant,
+    sessions: Arc<Vec<SessionSeats>>,
+}
+
+/// Last result of `load`, dropped after a short time or on any change to
+/// sessions or registrations
+pub struct SessionCache {
+    ttl: Duration,
+    // Bumped on every invalidation, so a load that raced with a change is not stored
+    generation: AtomicU64,
+    entry: Mutex<Option<CacheEntry>>,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            generation: AtomicU64::new(0),
+            entry: Mutex::new(None),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<CacheEntry>> {
+        self.entry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.lock() = None;
+    }
+
+    /// Cached sessions, loaded from the database when stale
+    pub async fn get(
+        &self,
+        conn: &mut AsyncMysqlConnection,
+    ) -> Result<Arc<Vec<SessionSeats>>, diesel::result::Error> {
+        let generation = AtomicU64::load(&self.generation, Ordering::SeqCst);
+        if let Some(entry) = self.lock().as_ref()
+            && entry.generation == generation
+            && entry.loaded_at.elapsed() < self.ttl
+        {
+            return Ok(entry.sessions.clone());
+        }
+
+        let sessions = Arc::new(load(conn).await?);
+
+        let mut entry = self.lock();
+        if AtomicU64::load(&self.generation, Ordering::SeqCst) == generation {
+            *entry = Some(CacheEntry {
+                generation,
+                loaded_at: Instant::now(),
+                sessions: sessions.clone(),
+            });
+        }
+        Ok(sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::test_db;
+
+    const SESSIONS: usize = 40;
+    const CAPACITY: usize = 30;
+
+    // The per-session COUNT loop that `load` replaced, kept as the reference result
+    async fn count_each_session(
+        conn: &mut AsyncMysqlConnection,
+        session_ids: &[i32],
+    ) -> HashMap<i32, i64> {
+        let mut counts = HashMap::new();
+        for &id in session_ids {
+            let count = registrations::table
+                .filter(registrations::session_id.eq(id))
+                .filter(booking::occupies_seat())
+                .count()
+                .get_result::<i64>(conn)
+                .await
+                .expect("Failed to count registrations");
+            counts.insert(id, count);
+        }
+        counts
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn grouped_load_matches_counting_each_session() {
+        let mut conn = test_db::connect().await;
+        let mut rng = StdRng::seed_from_u64(20);
+
+        let mut session_ids = Vec::with_capacity(SESSIONS);
+        for _ in 0..SESSIONS {
+            let session = test_db::insert_session(&mut conn, CAPACITY as i32).await;
+            session_ids.push(session.id);
+
+            let registered = rng.gen_range(0..=CAPACITY);
+            let confirmed = rng.gen_range(0..=registered);
+            let rows: Vec<_> = (0..registered)
+                .map(|n| test_db::new_registration(session.id, n))
+                .collect();
+            diesel::insert_into(registrations::table)
+                .values(&rows)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to insert test registrations");
+
+            let names: Vec<_> = rows[..confirmed]
+                .iter()
+                .map(|row| row.student_first_name.clone())
+                .collect();
+            diesel::update(
+                registrations::table
+                    .filter(registrations::session_id.eq(session.id))
+                    .filter(registrations::student_first_name.eq_any(names)),
+            )
+            .set(registrations::confirmed.eq(true))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to confirm test registrations");
+        }
+
+        let loaded = load(&mut conn).await;
+        let counted = count_each_session(&mut conn, &session_ids).await;
+
+        for id in &session_ids {
+            test_db::delete_session(&mut conn, *id).await;
+        }
+
+        let loaded: Vec<_> = loaded
+            .expect("Failed to load sessions")
+            .into_iter()
+            .filter(|(session, _)| session_ids.contains(&session.id))
+            .collect();
+        assert_eq!(loaded.len(), SESSIONS);
+        for (session, seats) in loaded {
+            assert_eq!(
+                Some(&seats),
+                counted.get(&session.id),
+                "session {}",
+                session.id
+            );
+        }
+    }
+}
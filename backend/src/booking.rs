@@ -0,0 +1,790 @@
+// Seat booking rules shared by every route that takes or frees a seat in a session.
+// The session row is locked with `SELECT ... FOR UPDATE` so the capacity check
+// and the write happen atomically, concurrent bookings queue up on the lock.
+
+use chrono::NaiveDateTime;
+use rocket::http::Status;
+use rocket::serde::json::json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::config::{AppConfig, DuplicateRule};
+use crate::error::ApiError;
+use crate::models::{NewRegistration, Registration, Session};
+use crate::schema::{registrations, sessions};
+
+/// Reasons a booking can be refused
+#[derive(Debug)]
+pub enum BookingError {
+    /// The session or registration does not exist
+    NotFound,
+    /// All seats of the session are taken
+    Full,
+    /// The confirmation link is no longer valid
+    Expired,
+    /// The student is already registered within the scope of the duplicate rule
+    Duplicate(DuplicateRule),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for BookingError {
+    fn from(e: diesel::result::Error) -> Self {
+        BookingError::Database(e)
+    }
+}
+
+impl From<BookingError> for ApiError {
+    fn from(e: BookingError) -> Self {
+        match e {
+            BookingError::NotFound => {
+                ApiError::not_found("not_found", "Termín alebo registrácia neexistuje")
+            }
+            BookingError::Full => ApiError::conflict("session_full", "Termín je už plne obsadený"),
+            BookingError::Expired => {
+                ApiError::new(Status::Gone, "link_expired", "Platnosť odkazu vypršala")
+            }
+            BookingError::Duplicate(rule) => ApiError::conflict(
+                "duplicate_registration",
+                "Žiak s rovnakým menom a emailom zákonného zástupcu je už prihlásený",
+            )
+            .with_details(json!({ "rule": rule.as_str() })),
+            BookingError::Database(e) => ApiError::internal("Database error during booking")(e),
+        }
+    }
+}
+
+/// Filter matching the registrations that hold a seat of their session
+pub type OccupiesSeat = diesel::dsl::Or<
+    diesel::dsl::Eq<registrations::confirmed, bool>,
+    diesel::dsl::And<
+        diesel::dsl::And<
+            diesel::dsl::Eq<registrations::confirmed, bool>,
+            diesel::dsl::Eq<registrations::waitlisted, bool>,
+        >,
+        diesel::dsl::IsNotNull<registrations::confirmation_token>,
+    >,
+>;
+
+/// A seat is held by a confirmed registration and by a pending one whose
+/// confirmation link is still out, so confirming it can't overbook the session
+pub fn occupies_seat() -> OccupiesSeat {
+    registrations::confirmed
+        .eq(true)
+        .or(registrations::confirmed
+            .eq(false)
+            .and(registrations::waitlisted.eq(false))
+            .and(registrations::confirmation_token.is_not_null()))
+}
+
+/// Number of seats of a session held by its registrations
+pub async fn occupied_seats(
+    conn: &mut AsyncMysqlConnection,
+    session_id: i32,
+) -> Result<i64, diesel::result::Error> {
+    registrations::table
+        .filter(registrations::session_id.eq(session_id))
+        .filter(occupies_seat())
+        .count()
+        .get_result(conn)
+        .await
+}
+
+/// Lock a session row for the rest of the transaction and count its occupied seats.
+/// Must be called inside a transaction, otherwise the lock is released immediately.
+pub async fn lock_session(
+    conn: &mut AsyncMysqlConnection,
+    session_id: i32,
+) -> Result<(Session, i64), BookingError> {
+    let session = sessions::table
+        .find(session_id)
+        .for_update()
+        .first::<Session>(conn)
+        .await
+        .optional()?
+        .ok_or(BookingError::NotFound)?;
+
+    let occupied = occupied_seats(conn, session_id).await?;
+
+    Ok((session, occupied))
+}
+
+/// Result of a successful booking
+#[derive(Debug, Clone, Copy)]
+pub struct Booking {
+    pub id: i32,
+    /// Set when the session was full and the registration joined its waitlist
+    pub waitlist_position: Option<i64>,
+}
+
+/// Student identity compared by the duplicate rule
+struct Student<'a> {
+    first_name: &'a str,
+    last_name: &'a str,
+    guardian_email: &'a str,
+}
+
+impl<'a> From<&'a NewRegistration> for Student<'a> {
+    fn from(registration: &'a NewRegistration) -> Self {
+        Student {
+            first_name: &registration.student_first_name,
+            last_name: &registration.student_last_name,
+            guardian_email: &registration.guardian_email,
+        }
+    }
+}
+
+/// Whether the student already holds a registration within the rule's scope
+/// in `session`, other than the `excluded` one
+async fn has_duplicate(
+    conn: &mut AsyncMysqlConnection,
+    student: Student<'_>,
+    session: &Session,
+    rule: DuplicateRule,
+    excluded: Option<i32>,
+) -> Result<bool, BookingError> {
+    let mut query = registrations::table
+        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
+        .filter(registrations::student_first_name.eq(student.first_name))
+        .filter(registrations::student_last_name.eq(student.last_name))
+        .filter(registrations::guardian_email.eq(student.guardian_email))
+        .into_boxed();
+
+    if let Some(excluded) = excluded {
+        query = query.filter(registrations::id.ne(excluded));
+    }
+
+    let query = match rule {
+        DuplicateRule::Off => return Ok(false),
+        DuplicateRule::Session => query.filter(registrations::session_id.eq(session.id)),
+        DuplicateRule::Turnus => query.filter(sessions::turnus.eq(session.turnus)),
+        DuplicateRule::Global => query,
+    };
+
+    let count: i64 = query.count().get_result(conn).await?;
+    Ok(count > 0)
+}
+
+/// Insert a registration inside an open transaction. A full session either
+/// refuses it or, when `allow_waitlist` is set, queues it on the waitlist.
+/// Registrations repeating a student within the `duplicate_rule` scope are refused.
+pub async fn insert_locked(
+    conn: &mut AsyncMysqlConnection,
+    mut new_registration: NewRegistration,
+    allow_waitlist: bool,
+    duplicate_rule: DuplicateRule,
+) -> Result<Booking, BookingError> {
+    let (session, occupied) = lock_session(conn, new_registration.session_id).await?;
+
+    if has_duplicate(
+        conn,
+        Student::from(&new_registration),
+        &session,
+        duplicate_rule,
+        None,
+    )
+    .await?
+    {
+        return Err(BookingError::Duplicate(duplicate_rule));
+    }
+
+    let full = occupied >= session.max_capacity as i64;
+    if full && !allow_waitlist {
+        return Err(BookingError::Full);
+    }
+
+    if full {
+        // Waitlisted entries are confirmed by promotion, not by email
+        new_registration.waitlisted = true;
+        new_registration.confirmation_token = None;
+        new_registration.confirmation_expires_at = None;
+    }
+
+    diesel::insert_into(registrations::table)
+        .values(&new_registration)
+        .execute(conn)
+        .await?;
+
+    let registration_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "LAST_INSERT_ID()",
+    ))
+    .get_result::<i32>(conn)
+    .await?;
+
+    let waitlist_position = if full {
+        Some(waitlist_position(conn, session.id, registration_id).await?)
+    } else {
+        None
+    };
+
+    Ok(Booking {
+        id: registration_id,
+        waitlist_position,
+    })
+}
+
+/// Insert a registration if its session still has a free seat
+pub async fn create_registration(
+    db: &mut AsyncMysqlConnection,
+    new_registration: NewRegistration,
+    allow_waitlist: bool,
+    duplicate_rule: DuplicateRule,
+) -> Result<Booking, BookingError> {
+    db.transaction(|conn| {
+        insert_locked(conn, new_registration, allow_waitlist, duplicate_rule).scope_boxed()
+    })
+    .await
+}
+
+/// Insert several registrations in one transaction, either all of them are
+/// booked or none. Bookings are returned in the order of `new_registrations`.
+pub async fn create_registrations(
+    db: &mut AsyncMysqlConnection,
+    new_registrations: Vec<NewRegistration>,
+    allow_waitlist: bool,
+    duplicate_rule: DuplicateRule,
+) -> Result<Vec<Booking>, BookingError> {
+    db.transaction(|conn| {
+        async move {
+            // Lock sessions in id order so concurrent batches can't deadlock
+            let mut order: Vec<usize> = (0..new_registrations.len()).collect();
+            order.sort_by_key(|&i| new_registrations[i].session_id);
+
+            let mut bookings = vec![None; new_registrations.len()];
+            for i in order {
+                let booking = insert_locked(
+                    conn,
+                    new_registrations[i].clone(),
+                    allow_waitlist,
+                    duplicate_rule,
+                )
+                .await?;
+                bookings[i] = Some(booking);
+            }
+            Ok(bookings.into_iter().flatten().collect())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// 1-based position of a waitlisted registration within its session's queue
+async fn waitlist_position(
+    conn: &mut AsyncMysqlConnection,
+    session_id: i32,
+    registration_id: i32,
+) -> Result<i64, BookingError> {
+    Ok(registrations::table
+        .filter(registrations::session_id.eq(session_id))
+        .filter(registrations::waitlisted.eq(true))
+        .filter(registrations::id.le(registration_id))
+        .count()
+        .get_result(conn)
+        .await?)
+}
+
+/// Waitlist positions (registration id -> 1-based position) for a set of loaded rows
+pub fn waitlist_positions(rows: &[(Registration, Session)]) -> HashMap<i32, i64> {
+    let mut waitlisted: Vec<(i32, i32)> = rows
+        .iter()
+        .filter(|(reg, _)| reg.waitlisted)
+        .map(|(reg, _)| (reg.session_id, reg.id))
+        .collect();
+    waitlisted.sort_unstable();
+
+    let mut positions = HashMap::new();
+    let mut current_session = None;
+    let mut position = 0;
+    for (session_id, registration_id) in waitlisted {
+        if current_session != Some(session_id) {
+            current_session = Some(session_id);
+            position = 0;
+        }
+        position += 1;
+        positions.insert(registration_id, position);
+    }
+    positions
+}
+
+/// Ids of loaded rows sharing the student name with another row, together with
+/// the guardian email or phone. Names and contacts are compared case-insensitively.
+pub fn suspected_duplicates(rows: &[(Registration, Session)]) -> HashSet<i32> {
+    let mut groups: HashMap<(String, String, String), Vec<i32>> = HashMap::new();
+    for (reg, _) in rows {
+        let student = (
+            reg.student_first_name.to_lowercase(),
+            reg.student_last_name.to_lowercase(),
+        );
+        for contact in [
+            reg.guardian_email.to_lowercase(),
+            reg.guardian_phone.clone(),
+        ] {
+            groups
+                .entry((student.0.clone(), student.1.clone(), contact))
+                .or_default()
+                .push(reg.id);
+        }
+    }
+
+    groups
+        .into_values()
+        .filter(|ids| ids.len() > 1)
+        .flatten()
+        .collect()
+}
+
+/// How registrations promoted from the waitlist get their seat
+#[derive(Debug, Clone, Copy)]
+pub enum Promotion {
+    /// The seat is taken right away
+    Direct,
+    /// The guardian confirms through a fresh emailed link valid until the given time
+    Pending(NaiveDateTime),
+}
+
+impl Promotion {
+    /// Promotions follow `email_confirmation` like new registrations do
+    pub fn from_config(config: &AppConfig, now: NaiveDateTime) -> Self {
+        if config.email_confirmation {
+            Promotion::Pending(now + chrono::Duration::minutes(config.confirmation_ttl_minutes))
+        } else {
+            Promotion::Direct
+        }
+    }
+}
+
+/// Fill free seats of a locked session from its waitlist, oldest entry first.
+/// Directly promoted registrations take the seat right away, pending ones get a
+/// new confirmation link and keep the seat offered until it expires. Returns their ids.
+pub async fn promote_waitlist(
+    conn: &mut AsyncMysqlConnection,
+    session_id: i32,
+    promotion: Promotion,
+) -> Result<Vec<i32>, BookingError> {
+    let (session, occupied) = lock_session(conn, session_id).await?;
+
+    let free_seats = session.max_capacity as i64 - occupied;
+    if free_seats <= 0 {
+        return Ok(Vec::new());
+    }
+
+    let promoted = registrations::table
+        .filter(registrations::session_id.eq(session_id))
+        .filter(registrations::waitlisted.eq(true))
+        .order(registrations::id.asc())
+        .limit(free_seats)
+        .select(registrations::id)
+        .for_update()
+        .load::<i32>(conn)
+        .await?;
+
+    match promotion {
+        Promotion::Direct if !promoted.is_empty() => {
+            diesel::update(registrations::table.filter(registrations::id.eq_any(&promoted)))
+                .set((
+                    registrations::waitlisted.eq(false),
+                    registrations::confirmed.eq(true),
+                ))
+                .execute(conn)
+                .await?;
+        }
+        Promotion::Direct => {}
+        Promotion::Pending(expires_at) => {
+            for id in &promoted {
+                diesel::update(registrations::table.find(id))
+                    .set((
+                        registrations::waitlisted.eq(false),
+                        registrations::confirmation_token.eq(Uuid::new_v4().simple().to_string()),
+                        registrations::confirmation_expires_at.eq(expires_at),
+                    ))
+                    .execute(conn)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(promoted)
+}
+
+/// Delete a registration and hand its seat to the waitlist.
+/// Returns the session id and the promoted registration ids.
+pub async fn delete_registration(
+    db: &mut AsyncMysqlConnection,
+    registration_id: i32,
+    promotion: Promotion,
+) -> Result<(i32, Vec<i32>), BookingError> {
+    db.transaction(|conn| {
+        async move {
+            let session_id = registrations::table
+                .find(registration_id)
+                .select(registrations::session_id)
+                .first::<i32>(conn)
+                .await
+                .optional()?
+                .ok_or(BookingError::NotFound)?;
+
+            lock_session(conn, session_id).await?;
+
+            diesel::delete(registrations::table.find(registration_id))
+                .execute(conn)
+                .await?;
+
+            let promoted = promote_waitlist(conn, session_id, promotion).await?;
+
+            Ok((session_id, promoted))
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Pending registrations removed by `expire_pending` and the seats they freed
+#[derive(Debug, Default)]
+pub struct Expired {
+    pub removed: usize,
+    /// Sessions that lost a pending registration
+    pub session_ids: Vec<i32>,
+    /// Registrations promoted from the waitlists of those sessions
+    pub promoted: Vec<i32>,
+}
+
+/// Delete pending registrations whose confirmation link ran out before `now`
+/// and hand the seats they held to the waitlist, one locked session at a time
+pub async fn expire_pending(
+    db: &mut AsyncMysqlConnection,
+    now: NaiveDateTime,
+    promotion: Promotion,
+) -> Result<Expired, BookingError> {
+    let session_ids = registrations::table
+        .filter(registrations::confirmed.eq(false))
+        .filter(registrations::confirmation_expires_at.lt(now))
+        .select(registrations::session_id)
+        .distinct()
+        .load::<i32>(db)
+        .await?;
+
+    let mut expired = Expired::default();
+    for session_id in session_ids {
+        let (removed, promoted) = db
+            .transaction(|conn| {
+                async move {
+                    lock_session(conn, session_id).await?;
+
+                    let removed = diesel::delete(
+                        registrations::table
+                            .filter(registrations::session_id.eq(session_id))
+                            .filter(registrations::confirmed.eq(false))
+                            .filter(registrations::confirmation_expires_at.lt(now)),
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    let promoted = promote_waitlist(conn, session_id, promotion).await?;
+
+                    Ok::<_, BookingError>((removed, promoted))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if removed > 0 || !promoted.is_empty() {
+            expired.removed += removed;
+            expired.session_ids.push(session_id);
+            expired.promoted.extend(promoted);
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Confirm a registration inside an open transaction, returns the session id
+async fn confirm_locked(
+    conn: &mut AsyncMysqlConnection,
+    registration_id: i32,
+) -> Result<i32, BookingError> {
+    let session_id = registrations::table
+        .find(registration_id)
+        .select(registrations::session_id)
+        .first::<i32>(conn)
+        .await
+        .optional()?
+        .ok_or(BookingError::NotFound)?;
+
+    // Always lock the session before the registration to keep a single lock order
+    let (session, occupied) = lock_session(conn, session_id).await?;
+
+    let (confirmed, holds_seat) = registrations::table
+        .find(registration_id)
+        .select((registrations::confirmed, occupies_seat()))
+        .for_update()
+        .first::<(bool, bool)>(conn)
+        .await
+        .optional()?
+        .ok_or(BookingError::NotFound)?;
+
+    if confirmed {
+        return Ok(session.id);
+    }
+
+    // A pending registration already counts in `occupied` and keeps its own seat
+    if !holds_seat && occupied >= session.max_capacity as i64 {
+        return Err(BookingError::Full);
+    }
+
+    // A confirmed registration no longer needs its email token
+    diesel::update(registrations::table.find(registration_id))
+        .set((
+            registrations::confirmed.eq(true),
+            registrations::waitlisted.eq(false),
+            registrations::confirmation_token.eq(None::<String>),
+            registrations::confirmation_expires_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(session.id)
+}
+
+/// Confirm a registration unless its session is already full, returns the session id
+pub async fn confirm_registration(
+    db: &mut AsyncMysqlConnection,
+    registration_id: i32,
+) -> Result<i32, BookingError> {
+    db.transaction(|conn| confirm_locked(conn, registration_id).scope_boxed())
+        .await
+}
+
+/// Confirm the registration owning a one-time email token, returns the session id
+pub async fn confirm_by_token(
+    db: &mut AsyncMysqlConnection,
+    token: String,
+    now: NaiveDateTime,
+) -> Result<i32, BookingError> {
+    db.transaction(|conn| {
+        async move {
+            let (registration_id, expires_at) = registrations::table
+                .filter(registrations::confirmation_token.eq(&token))
+                .select((registrations::id, registrations::confirmation_expires_at))
+                .first::<(i32, Option<NaiveDateTime>)>(conn)
+                .await
+                .optional()?
+                .ok_or(BookingError::NotFound)?;
+
+            if expires_at.is_some_and(|expires_at| expires_at < now) {
+                return Err(BookingError::Expired);
+            }
+
+            confirm_locked(conn, registration_id).await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Cancel the registration owning a management token.
+/// Returns the freed session id and the registration ids promoted from its waitlist.
+pub async fn cancel_registration(
+    db: &mut AsyncMysqlConnection,
+    token: String,
+    promotion: Promotion,
+) -> Result<(i32, Vec<i32>), BookingError> {
+    let registration_id = registrations::table
+        .filter(registrations::manage_token.eq(&token))
+        .select(registrations::id)
+        .first::<i32>(db)
+        .await
+        .optional()?
+        .ok_or(BookingError::NotFound)?;
+
+    delete_registration(db, registration_id, promotion).await
+}
+
+/// Move the registration owning a management token to another session.
+/// The target must have a free seat and pass the duplicate rule, same as
+/// for a new registration. Returns the previous and the new session id and the registration ids
+/// promoted from the previous session's waitlist.
+pub async fn move_registration(
+    db: &mut AsyncMysqlConnection,
+    token: String,
+    target_session_id: i32,
+    promotion: Promotion,
+    duplicate_rule: DuplicateRule,
+) -> Result<(i32, i32, Vec<i32>), BookingError> {
+    db.transaction(|conn| {
+        async move {
+            let (registration_id, current_session_id, waitlisted, first_name, last_name, email) =
+                registrations::table
+                    .filter(registrations::manage_token.eq(&token))
+                    .select((
+                        registrations::id,
+                        registrations::session_id,
+                        registrations::waitlisted,
+                        registrations::student_first_name,
+                        registrations::student_last_name,
+                        registrations::guardian_email,
+                    ))
+                    .first::<(i32, i32, bool, String, String, String)>(conn)
+                    .await
+                    .optional()?
+                    .ok_or(BookingError::NotFound)?;
+
+            if current_session_id == target_session_id {
+                return Ok((current_session_id, target_session_id, Vec::new()));
+            }
+
+            // Lock both sessions in id order so two opposite moves cannot deadlock
+            let (target_session, occupied) = if current_session_id < target_session_id {
+                lock_session(conn, current_session_id).await?;
+                lock_session(conn, target_session_id).await?
+            } else {
+                let target = lock_session(conn, target_session_id).await?;
+                lock_session(conn, current_session_id).await?;
+                target
+            };
+
+            let student = Student {
+                first_name: &first_name,
+                last_name: &last_name,
+                guardian_email: &email,
+            };
+            if has_duplicate(
+                conn,
+                student,
+                &target_session,
+                duplicate_rule,
+                Some(registration_id),
+            )
+            .await?
+            {
+                return Err(BookingError::Duplicate(duplicate_rule));
+            }
+
+            if occupied >= target_session.max_capacity as i64 {
+                return Err(BookingError::Full);
+            }
+
+            // A waitlisted entry moving into a free seat is promoted on the spot
+            if waitlisted {
+                diesel::update(registrations::table.find(registration_id))
+                    .set((
+                        registrations::session_id.eq(target_session_id),
+                        registrations::waitlisted.eq(false),
+                        registrations::confirmed.eq(true),
+                    ))
+                    .execute(conn)
+                    .await?;
+            } else {
+                diesel::update(registrations::table.find(registration_id))
+                    .set(registrations::session_id.eq(target_session_id))
+                    .execute(conn)
+                    .await?;
+            }
+
+            let promoted = promote_waitlist(conn, current_session_id, promotion).await?;
+
+            Ok((current_session_id, target_session_id, promoted))
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    // Every task registers and confirms right away, the test registrations carry
+    // no confirmation link so only confirming takes the seat. Exactly one of them
+    // may get the last seat.
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn parallel_bookings_take_the_last_seat_once() {
+        const PARALLEL: usize = 20;
+
+        let mut conn = test_db::connect().await;
+        let session = test_db::insert_session(&mut conn, 1).await;
+
+        let tasks: Vec<_> = (0..PARALLEL)
+            .map(|n| {
+                rocket::tokio::spawn(async move {
+                    let mut conn = AsyncMysqlConnection::establish(&test_db::database_url())
+                        .await
+                        .expect("Failed to connect to the test database");
+                    let booking = create_registration(
+                        &mut conn,
+                        test_db::new_registration(session.id, n),
+                        false,
+                        DuplicateRule::Off,
+                    )
+                    .await?;
+                    confirm_registration(&mut conn, booking.id).await
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(PARALLEL);
+        for task in tasks {
+            results.push(task.await.expect("Booking task panicked"));
+        }
+        test_db::delete_session(&mut conn, session.id).await;
+
+        let booked = results.iter().filter(|result| result.is_ok()).count();
+        let full = results
+            .iter()
+            .filter(|result| matches!(result, Err(BookingError::Full)))
+            .count();
+        assert_eq!(booked, 1, "results: {:?}", results);
+        assert_eq!(full, PARALLEL - 1, "results: {:?}", results);
+    }
+
+    // The first waitlisted entry was offered the seat but never confirmed it,
+    // once its link runs out the seat goes to the next one in the queue
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn expired_promotion_passes_the_seat_on() {
+        let mut conn = test_db::connect().await;
+        let session = test_db::insert_session(&mut conn, 1).await;
+        let now = chrono::Local::now().naive_local();
+
+        let offered = NewRegistration {
+            confirmation_token: Some(Uuid::new_v4().simple().to_string()),
+            confirmation_expires_at: Some(now - chrono::Duration::minutes(1)),
+            ..test_db::new_registration(session.id, 0)
+        };
+        let next = NewRegistration {
+            waitlisted: true,
+            ..test_db::new_registration(session.id, 1)
+        };
+        diesel::insert_into(registrations::table)
+            .values(&vec![offered, next])
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert test registrations");
+
+        let expired = expire_pending(&mut conn, now, Promotion::Pending(now)).await;
+        let remaining = registrations::table
+            .filter(registrations::session_id.eq(session.id))
+            .select((
+                registrations::id,
+                registrations::student_first_name,
+                registrations::waitlisted,
+                registrations::confirmation_token.is_not_null(),
+            ))
+            .load::<(i32, String, bool, bool)>(&mut conn)
+            .await
+            .expect("Failed to load test registrations");
+        test_db::delete_session(&mut conn, session.id).await;
+
+        let expired = expired.expect("Failed to expire pending registrations");
+        assert_eq!(expired.removed, 1);
+        assert_eq!(expired.session_ids, vec![session.id]);
+        assert_eq!(remaining.len(), 1, "remaining: {:?}", remaining);
+        let (id, name, waitlisted, has_token) = &remaining[0];
+        assert_eq!(name, "Student1");
+        assert!(!waitlisted && *has_token, "remaining: {:?}", remaining);
+        assert_eq!(expired.promoted, vec![*id]);
+    }
+}
@@ -0,0 +1,120 @@
+// Check-in at the event: the QR code handed to guardians and marking
+// attendance when it is scanned at the entrance
+
+use chrono::NaiveDateTime;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use rocket::serde::json::json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+
+use crate::error::ApiError;
+use crate::models::{Registration, Session};
+use crate::schema::{registrations, sessions};
+
+#[derive(Debug)]
+pub enum CheckinError {
+    /// No registration has the code
+    Unknown,
+    /// The registration is unconfirmed or on the waitlist, the student has no seat
+    NotConfirmed,
+    /// The code was scanned before, at the given time
+    AlreadyCheckedIn(NaiveDateTime),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for CheckinError {
+    fn from(e: diesel::result::Error) -> Self {
+        CheckinError::Database(e)
+    }
+}
+
+impl From<CheckinError> for ApiError {
+    fn from(e: CheckinError) -> Self {
+        match e {
+            CheckinError::Unknown => ApiError::not_found("unknown_code", "Neznámy kód"),
+            CheckinError::NotConfirmed => ApiError::conflict(
+                "not_confirmed",
+                "Prihláška nie je potvrdená alebo je žiak náhradník",
+            ),
+            CheckinError::AlreadyCheckedIn(at) => ApiError::conflict(
+                "already_checked_in",
+                format!("Kód už bol použitý o {}", at.format("%H:%M")),
+            )
+            .with_details(json!({ "checked_in_at": at })),
+            CheckinError::Database(e) => ApiError::internal("Database error during check-in")(e),
+        }
+    }
+}
+
+/// QR code of a check-in code as SVG, the code itself is all it contains
+pub fn qr_svg(code: &str) -> Option<String> {
+    Some(
+        QrCode::new(code.as_bytes())
+            .ok()?
+            .render::<svg::Color>()
+            .min_dimensions(240, 240)
+            .build(),
+    )
+}
+
+/// Check-in code of the registration owning a management token, only once
+/// the student has a confirmed seat
+pub async fn code_for_manage_token(
+    conn: &mut AsyncMysqlConnection,
+    token: &str,
+) -> Result<String, CheckinError> {
+    let (code, confirmed, waitlisted) = registrations::table
+        .filter(registrations::manage_token.eq(token))
+        .select((
+            registrations::checkin_code,
+            registrations::confirmed,
+            registrations::waitlisted,
+        ))
+        .first::<(Option<String>, bool, bool)>(conn)
+        .await
+        .optional()?
+        .ok_or(CheckinError::Unknown)?;
+
+    match code {
+        Some(code) if confirmed && !waitlisted => Ok(code),
+        Some(_) => Err(CheckinError::NotConfirmed),
+        None => Err(CheckinError::Unknown),
+    }
+}
+
+/// Mark the registration with `code` as attended at `now`. A code works once,
+/// the conditional update keeps two scans at the same moment from both passing.
+pub async fn check_in(
+    conn: &mut AsyncMysqlConnection,
+    code: &str,
+    now: NaiveDateTime,
+) -> Result<(Registration, Session), CheckinError> {
+    let updated = diesel::update(
+        registrations::table
+            .filter(registrations::checkin_code.eq(code))
+            .filter(registrations::checked_in_at.is_null())
+            .filter(registrations::confirmed.eq(true))
+            .filter(registrations::waitlisted.eq(false)),
+    )
+    .set(registrations::checked_in_at.eq(now))
+    .execute(conn)
+    .await?;
+
+    let (registration, session) = registrations::table
+        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
+        .filter(registrations::checkin_code.eq(code))
+        .select((Registration::as_select(), Session::as_select()))
+        .first::<(Registration, Session)>(conn)
+        .await
+        .optional()?
+        .ok_or(CheckinError::Unknown)?;
+
+    if updated > 0 {
+        return Ok((registration, session));
+    }
+    match registration.checked_in_at {
+        Some(at) => Err(CheckinError::AlreadyCheckedIn(at)),
+        None => Err(CheckinError::NotConfirmed),
+    }
+}
@@ -0,0 +1,38 @@
+// Source of the current time for rules that depend on the date, such as
+// which sessions can still be booked
+
+use chrono::NaiveDateTime;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Clock {
+    /// Local wall clock time
+    System,
+    /// Always the same moment, for tests of date dependent behaviour
+    #[cfg(test)]
+    Fixed(NaiveDateTime),
+}
+
+impl Clock {
+    pub fn now(&self) -> NaiveDateTime {
+        match self {
+            Clock::System => chrono::Local::now().naive_local(),
+            #[cfg(test)]
+            Clock::Fixed(time) => *time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_stands_still() {
+        let time =
+            NaiveDateTime::parse_from_str("2026-03-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let clock = Clock::Fixed(time);
+
+        assert_eq!(clock.now(), time);
+        assert_eq!(clock.now(), time);
+    }
+}
@@ -5,14 +5,151 @@ use rocket::figment::{Figment, providers::{Env, Format, Toml}};
 pub struct AppConfig {
     #[serde(alias = "DATABASE_URL")]
     pub database_url: String,
-    #[serde(alias = "ADMIN_PASSWORD_HASH")]
-    pub admin_password_hash: String,
+    /// Bootstrap manager account, created when no admin user exists yet
+    #[serde(default, alias = "ADMIN_PASSWORD_HASH")]
+    pub admin_password_hash: Option<String>,
+    #[serde(default = "default_admin_username", alias = "ADMIN_USERNAME")]
+    pub admin_username: String,
+    /// Hard limit on an admin session's lifetime, regardless of activity
+    #[serde(default = "default_admin_session_ttl_minutes")]
+    pub admin_session_ttl_minutes: i64,
+    /// Admin sessions unused for this long are logged out
+    #[serde(default = "default_admin_session_idle_minutes")]
+    pub admin_session_idle_minutes: i64,
+    /// Failed admin logins allowed per IP before exponential lockout kicks in
+    #[serde(default = "default_login_max_attempts")]
+    pub login_max_attempts: u32,
+    /// Failed admin logins from all clients within 15 minutes before clients with
+    /// failures on record are paused
+    #[serde(default = "default_login_global_max_failures")]
+    pub login_global_max_failures: usize,
+    /// Registration attempts allowed per IP within an hour
+    #[serde(default = "default_register_max_per_ip")]
+    pub register_max_per_ip: usize,
+    /// Minimum seconds between loading the form and submitting it
+    #[serde(default = "default_register_min_fill_seconds")]
+    pub register_min_fill_seconds: u64,
+    /// Key for signing form tokens, random per process when unset
+    #[serde(default, alias = "FORM_TOKEN_SECRET")]
+    pub form_token_secret: Option<String>,
     #[serde(default = "default_rocket_port", alias = "ROCKET_PORT")]
     pub rocket_port: u16,
     #[serde(default = "default_rocket_address", alias = "ROCKET_ADDRESS")]
     pub rocket_address: String,
     #[serde(default = "default_static_dir", alias = "STATIC_DIR")]
     pub static_dir: String,
+    /// Public URL of the site, used to build links in emails
+    #[serde(default = "default_public_base_url", alias = "PUBLIC_BASE_URL")]
+    pub public_base_url: String,
+    /// Require guardians to confirm their registration via an emailed link
+    #[serde(default, alias = "EMAIL_CONFIRMATION")]
+    pub email_confirmation: bool,
+    /// How long a confirmation link stays valid before the pending registration is removed
+    #[serde(default = "default_confirmation_ttl_minutes")]
+    pub confirmation_ttl_minutes: i64,
+    /// Let full sessions accept registrations onto a waitlist
+    #[serde(default, alias = "WAITLIST_ENABLED")]
+    pub waitlist_enabled: bool,
+    /// Email guardians when their waitlisted registration gets a seat. With
+    /// `email_confirmation` they are always emailed a link to confirm it.
+    #[serde(default)]
+    pub waitlist_notify: bool,
+    /// Bookings close this many hours before a session starts
+    #[serde(default)]
+    pub booking_cutoff_hours: i64,
+    /// Seconds the public session listing with seat counts is cached, 0 disables caching
+    #[serde(default = "default_sessions_cache_seconds")]
+    pub sessions_cache_seconds: u64,
+    /// TrueType font for PDF attendance sheets. Without it the built-in Helvetica is
+    /// used, which lacks some Slovak letters such as č, ľ or ť.
+    #[serde(default, alias = "PDF_FONT_PATH")]
+    pub pdf_font_path: Option<String>,
+    /// Scope in which a student may hold only one registration
+    #[serde(default, alias = "DUPLICATE_RULE")]
+    pub duplicate_rule: DuplicateRule,
+    #[serde(default)]
+    pub mail_transport: MailTransportKind,
+    #[serde(default = "default_mail_from", alias = "MAIL_FROM")]
+    pub mail_from: String,
+    /// Directory for `.eml` files when `mail_transport = "file"`
+    #[serde(default = "default_mail_dir")]
+    pub mail_dir: String,
+    #[serde(default, alias = "SMTP_HOST")]
+    pub smtp_host: Option<String>,
+    #[serde(default = "default_smtp_port", alias = "SMTP_PORT")]
+    pub smtp_port: u16,
+    #[serde(default, alias = "SMTP_USERNAME")]
+    pub smtp_username: Option<String>,
+    #[serde(default, alias = "SMTP_PASSWORD")]
+    pub smtp_password: Option<String>,
+}
+
+/// Where outgoing mail is delivered
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransportKind {
+    Smtp,
+    File,
+    #[default]
+    Log,
+}
+
+/// Scope of the duplicate check on new registrations. A duplicate is another
+/// registration with the same student name and guardian email.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateRule {
+    Off,
+    /// Once per session
+    #[default]
+    Session,
+    /// Once per turnus, across all its sessions
+    Turnus,
+    /// Once in total
+    Global,
+}
+
+impl DuplicateRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateRule::Off => "off",
+            DuplicateRule::Session => "session",
+            DuplicateRule::Turnus => "turnus",
+            DuplicateRule::Global => "global",
+        }
+    }
+}
+
+fn default_admin_username() -> String {
+    "admin".to_string()
+}
+
+fn default_admin_session_ttl_minutes() -> i64 {
+    12 * 60
+}
+
+fn default_admin_session_idle_minutes() -> i64 {
+    60
+}
+
+fn default_login_max_attempts() -> u32 {
+    5
+}
+
+fn default_login_global_max_failures() -> usize {
+    100
+}
+
+fn default_register_max_per_ip() -> usize {
+    30
+}
+
+fn default_register_min_fill_seconds() -> u64 {
+    3
+}
+
+fn default_sessions_cache_seconds() -> u64 {
+    5
 }
 
 fn default_rocket_port() -> u16 {
@@ -27,13 +164,50 @@ fn default_static_dir() -> String {
     "static".to_string()
 }
 
+fn default_public_base_url() -> String {
+    "http://localhost:8000".to_string()
+}
+
+fn default_confirmation_ttl_minutes() -> i64 {
+    24 * 60
+}
+
+fn default_mail_from() -> String {
+    "noreply@localhost".to_string()
+}
+
+fn default_mail_dir() -> String {
+    "mail".to_string()
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
 impl AppConfig {
     pub fn load() -> Self {
         Figment::new()
             .merge(Toml::file("Config.toml"))
             .merge(Toml::file("../Config.toml"))
-            .merge(Env::raw().only(&["DATABASE_URL", "ADMIN_PASSWORD_HASH", "ROCKET_PORT", "ROCKET_ADDRESS"]))
+            .merge(Env::raw().only(&[
+                "DATABASE_URL",
+                "ADMIN_PASSWORD_HASH",
+                "ADMIN_USERNAME",
+                "ROCKET_PORT",
+                "ROCKET_ADDRESS",
+                "PUBLIC_BASE_URL",
+                "EMAIL_CONFIRMATION",
+                "WAITLIST_ENABLED",
+                "DUPLICATE_RULE",
+                "MAIL_FROM",
+                "SMTP_HOST",
+                "SMTP_PORT",
+                "SMTP_USERNAME",
+                "SMTP_PASSWORD",
+                "FORM_TOKEN_SECRET",
+                "PDF_FONT_PATH",
+            ]))
             .extract()
-            .expect("Failed to load configuration. Ensure Config.toml exists or environment variables are set (DATABASE_URL, ADMIN_PASSWORD_HASH).")
+            .expect("Failed to load configuration. Ensure Config.toml exists or environment variables are set (DATABASE_URL).")
     }
 }
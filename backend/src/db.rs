@@ -6,6 +6,7 @@ use rocket::Rocket;
 use rocket_db_pools::Database;
 use rocket_db_pools::diesel::MysqlPool;
 use crate::config::AppConfig;
+use crate::models::{AdminRole, NewAdminUser};
 
 /// Database connection pool for registration system
 #[derive(Database)]
@@ -13,7 +14,7 @@ use crate::config::AppConfig;
 pub struct RegistrationDB(MysqlPool);
 
 // Embed migrations from the migrations directory
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 /// Run pending database migrations
 pub async fn run_migrations(rocket: Rocket<rocket::Build>) -> Rocket<rocket::Build> {
@@ -59,3 +60,55 @@ pub async fn run_migrations(rocket: Rocket<rocket::Build>) -> Rocket<rocket::Bui
 
     rocket
 }
+
+/// Create the bootstrap manager account from the config when no admin user exists yet
+pub async fn bootstrap_admin(rocket: Rocket<rocket::Build>) -> Rocket<rocket::Build> {
+    let config = rocket.state::<AppConfig>()
+        .expect("AppConfig not managed")
+        .clone();
+
+    let Some(password_hash) = config.admin_password_hash.clone() else {
+        return rocket;
+    };
+
+    let result: Result<bool, String> = rocket::tokio::task::spawn_blocking(move || {
+        use crate::schema::admin_users;
+        use diesel::prelude::*;
+
+        let mut sync_conn = diesel::MysqlConnection::establish(&config.database_url)
+            .map_err(|e| format!("Failed to establish connection: {}", e))?;
+
+        let existing: i64 = admin_users::table
+            .count()
+            .get_result(&mut sync_conn)
+            .map_err(|e| format!("Failed to count admin users: {}", e))?;
+
+        if existing > 0 {
+            return Ok(false);
+        }
+
+        diesel::insert_into(admin_users::table)
+            .values(&NewAdminUser {
+                username: config.admin_username.clone(),
+                password_hash,
+                role: AdminRole::Manager.as_str().to_string(),
+            })
+            .execute(&mut sync_conn)
+            .map_err(|e| format!("Failed to create bootstrap admin: {}", e))?;
+
+        Ok(true)
+    })
+    .await
+    .expect("Bootstrap admin task panicked");
+
+    match result {
+        Ok(true) => println!("✅ Created bootstrap admin account"),
+        Ok(false) => {}
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            panic!("Admin bootstrap failed");
+        }
+    }
+
+    rocket
+}
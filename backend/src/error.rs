@@ -0,0 +1,138 @@
+// JSON error responses shared by every API route
+
+use rocket::http::{Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::Serialize;
+use rocket::serde::json::{Json, Value};
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Error returned by API routes, rendered as
+/// `{"code": "...", "message": "...", "details": {...}}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: Status,
+    /// Machine readable error code, e.g. `session_full`
+    code: &'static str,
+    /// Human readable message that can be shown to the user
+    message: String,
+    details: Option<Value>,
+    /// Seconds for the `Retry-After` header
+    retry_after: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ApiErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a Value>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Status::BadRequest, code, message)
+    }
+
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Status::NotFound, code, message)
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Status::Conflict, code, message)
+    }
+
+    pub fn unprocessable(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Status::UnprocessableEntity, code, message)
+    }
+
+    /// Registration is switched off by the admins
+    pub fn registration_closed() -> Self {
+        Self::new(
+            Status::PreconditionFailed,
+            "registration_closed",
+            "Registrácia je momentálne uzavretá",
+        )
+    }
+
+    /// `429 Too Many Requests` telling the client when to come back
+    pub fn too_many_requests(wait: Duration) -> Self {
+        // Round up so clients never come back a moment too early
+        let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        let mut error = Self::new(
+            Status::TooManyRequests,
+            "too_many_requests",
+            "Príliš veľa pokusov, skúste to znova neskôr",
+        );
+        error.retry_after = Some(secs);
+        error
+    }
+
+    /// Log an unexpected failure and hide its details from the client.
+    /// Meant for `map_err`, e.g. `.map_err(ApiError::internal("Error loading sessions"))`
+    pub fn internal<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| {
+            eprintln!("{}: {}", context, e);
+            Self::from(Status::InternalServerError)
+        }
+    }
+}
+
+/// Generic error for a bare status code, used by catchers and request guards
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        let (code, message) = match status.code {
+            401 => ("unauthorized", "Je potrebné prihlásenie"),
+            403 => ("forbidden", "Na túto akciu nemáte oprávnenie"),
+            404 => ("not_found", "Požadovaný záznam neexistuje"),
+            409 => ("conflict", "Požiadavka je v konflikte s aktuálnym stavom"),
+            412 => ("precondition_failed", "Akcia momentálne nie je dostupná"),
+            413 => ("payload_too_large", "Požiadavka je príliš veľká"),
+            415 => ("unsupported_media_type", "Nepodporovaný formát požiadavky"),
+            422 => ("unprocessable_entity", "Požiadavka obsahuje neplatné údaje"),
+            429 => (
+                "too_many_requests",
+                "Príliš veľa pokusov, skúste to znova neskôr",
+            ),
+            503 => ("service_unavailable", "Služba je dočasne nedostupná"),
+            400..=499 => ("bad_request", "Neplatná požiadavka"),
+            _ => ("internal_error", "Nastala neočakávaná chyba servera"),
+        };
+        Self::new(status, code, message)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let body = Json(ApiErrorBody {
+            code: self.code,
+            message: &self.message,
+            details: self.details.as_ref(),
+        });
+
+        let mut response = Response::build_from(body.respond_to(req)?)
+            .status(self.status)
+            .finalize();
+        if let Some(secs) = self.retry_after {
+            response.set_header(Header::new("Retry-After", secs.to_string()));
+        }
+        Ok(response)
+    }
+}
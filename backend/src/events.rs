@@ -0,0 +1,74 @@
+// Live events pushed to connected browsers over the WebSocket endpoint
+
+use rocket::serde::Serialize;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use tokio::sync::broadcast;
+
+use crate::AppState;
+use crate::availability::SessionCache;
+use crate::booking;
+use crate::schema::sessions;
+
+/// Event broadcast to every subscriber of `/api/ws`
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde", tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// Registration was opened or closed by an admin
+    RegistrationStatus { enabled: bool },
+    /// Number of free seats of a session changed
+    Availability {
+        session_id: i32,
+        available_spots: i32,
+    },
+    /// The client missed events and has to reload the sessions
+    Resync,
+}
+
+/// Recount the occupied seats of a session and broadcast its availability.
+/// Also drops the cached session listing, so call it after every change to seats.
+/// Failures are only logged, a missed update must never fail the request itself.
+pub async fn publish_availability(
+    db: &mut AsyncMysqlConnection,
+    state: &AppState,
+    session_id: i32,
+) {
+    broadcast_availability(db, &state.tx, &state.session_cache, session_id).await;
+}
+
+/// `publish_availability` for background tasks holding only the channel and the cache
+pub async fn broadcast_availability(
+    db: &mut AsyncMysqlConnection,
+    tx: &broadcast::Sender<LiveEvent>,
+    session_cache: &SessionCache,
+    session_id: i32,
+) {
+    session_cache.invalidate();
+
+    let max_capacity = match sessions::table
+        .find(session_id)
+        .select(sessions::max_capacity)
+        .first::<i32>(db)
+        .await
+    {
+        Ok(max_capacity) => max_capacity,
+        Err(e) => {
+            eprintln!("Error loading session for live update: {}", e);
+            return;
+        }
+    };
+
+    let occupied = match booking::occupied_seats(db, session_id).await {
+        Ok(count) => count,
+        Err(e) => {
+            eprintln!("Error counting registrations for live update: {}", e);
+            return;
+        }
+    };
+
+    // Sending only fails when nobody is listening, which is fine
+    let _ = tx.send(LiveEvent::Availability {
+        session_id,
+        available_spots: max_capacity - occupied as i32,
+    });
+}
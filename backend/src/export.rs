@@ -0,0 +1,621 @@
+// Registration exports: one query and one column set, written as Excel, CSV or JSON Lines
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rocket::http::ContentType;
+use rocket::serde::Serialize;
+use rocket::serde::json::serde_json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::booking;
+use crate::models::{Registration, Session};
+use crate::schema::{registrations, sessions};
+
+/// Format of `GET /api/admin/registrations/export`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromFormField)]
+pub enum ExportFormat {
+    #[default]
+    Xlsx,
+    /// UTF-8 with a BOM and `;` separators, the way Slovak Excel opens it
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn content_type(self) -> ContentType {
+        match self {
+            ExportFormat::Xlsx => ContentType::new(
+                "application",
+                "vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ),
+            ExportFormat::Csv => ContentType::CSV.with_params(("charset", "utf-8")),
+            ExportFormat::Jsonl => ContentType::new("application", "x-ndjson"),
+        }
+    }
+}
+
+/// How the Excel export splits registrations into worksheets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromFormField)]
+pub enum SheetGrouping {
+    /// Every registration on one sheet
+    #[default]
+    None,
+    /// One sheet per turnus
+    Turnus,
+    /// One sheet per field code
+    Field,
+}
+
+impl SheetGrouping {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SheetGrouping::None => "none",
+            SheetGrouping::Turnus => "turnus",
+            SheetGrouping::Field => "field",
+        }
+    }
+}
+
+/// Column headers, in the order of the `ExportRecord` fields
+pub const HEADERS: [&str; 17] = [
+    "ID",
+    "Meno študenta",
+    "Priezvisko študenta",
+    "Meno zákonného zástupcu",
+    "Priezvisko zákonného zástupcu",
+    "Email",
+    "Telefón",
+    "Turnus",
+    "Dátum",
+    "Začiatok",
+    "Koniec",
+    "Odbor",
+    "Názov odboru",
+    "Potvrdené",
+    "Vytvorené",
+    "Poradie náhradníka",
+    "Príchod",
+];
+
+/// Registrations with their sessions, only confirmed ones unless `include_unconfirmed`
+pub async fn load_rows(
+    conn: &mut AsyncMysqlConnection,
+    include_unconfirmed: bool,
+) -> Result<Vec<(Registration, Session)>, diesel::result::Error> {
+    let mut query = registrations::table
+        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
+        .select((Registration::as_select(), Session::as_select()))
+        .into_boxed();
+
+    if !include_unconfirmed {
+        query = query.filter(registrations::confirmed.eq(true));
+    }
+
+    query.load::<(Registration, Session)>(conn).await
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ExportStatus {
+    Confirmed,
+    Waitlisted,
+    Unconfirmed,
+}
+
+impl ExportStatus {
+    fn label(self) -> &'static str {
+        match self {
+            ExportStatus::Confirmed => "Áno",
+            ExportStatus::Waitlisted => "Náhradník",
+            ExportStatus::Unconfirmed => "Nie",
+        }
+    }
+}
+
+/// One exported registration, the same columns in every format
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportRecord<'a> {
+    pub id: i32,
+    pub student_first_name: &'a str,
+    pub student_last_name: &'a str,
+    pub guardian_first_name: &'a str,
+    pub guardian_last_name: &'a str,
+    pub guardian_email: &'a str,
+    pub guardian_phone: &'a str,
+    pub turnus: i32,
+    pub session_date: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub field_code: &'a str,
+    pub field_name: &'a str,
+    pub status: ExportStatus,
+    pub created_at: Option<NaiveDateTime>,
+    pub waitlist_position: Option<i64>,
+    /// When the student was checked in at the event
+    pub checked_in_at: Option<NaiveDateTime>,
+}
+
+impl ExportRecord<'_> {
+    /// Cell texts for the text formats, in `HEADERS` order
+    fn texts(&self) -> [String; 17] {
+        [
+            self.id.to_string(),
+            self.student_first_name.to_string(),
+            self.student_last_name.to_string(),
+            self.guardian_first_name.to_string(),
+            self.guardian_last_name.to_string(),
+            self.guardian_email.to_string(),
+            self.guardian_phone.to_string(),
+            self.turnus.to_string(),
+            self.session_date.format("%d.%m.%Y").to_string(),
+            self.start_time.format("%H:%M").to_string(),
+            self.end_time.format("%H:%M").to_string(),
+            self.field_code.to_string(),
+            self.field_name.to_string(),
+            self.status.label().to_string(),
+            self.created_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            self.waitlist_position
+                .map(|position| position.to_string())
+                .unwrap_or_default(),
+            self.checked_in_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+pub fn records(rows: &[(Registration, Session)]) -> Vec<ExportRecord<'_>> {
+    let positions = booking::waitlist_positions(rows);
+
+    rows.iter()
+        .map(|(reg, session)| ExportRecord {
+            id: reg.id,
+            student_first_name: &reg.student_first_name,
+            student_last_name: &reg.student_last_name,
+            guardian_first_name: &reg.guardian_first_name,
+            guardian_last_name: &reg.guardian_last_name,
+            guardian_email: &reg.guardian_email,
+            guardian_phone: &reg.guardian_phone,
+            turnus: session.turnus,
+            session_date: session.session_date,
+            start_time: session.start_time,
+            end_time: session.end_time,
+            field_code: &session.field_code,
+            field_name: &session.field_name,
+            status: if reg.confirmed {
+                ExportStatus::Confirmed
+            } else if reg.waitlisted {
+                ExportStatus::Waitlisted
+            } else {
+                ExportStatus::Unconfirmed
+            },
+            created_at: reg.created_at,
+            waitlist_position: positions.get(&reg.id).copied(),
+            checked_in_at: reg.checked_in_at,
+        })
+        .collect()
+}
+
+/// Seat counts of one session for the summary sheet
+pub struct SessionSummary {
+    pub session: Session,
+    pub confirmed: i64,
+    pub pending: i64,
+    pub waitlisted: i64,
+    pub checked_in: i64,
+    /// Confirmed students who were not checked in, only known once the session has ended
+    pub no_shows: Option<i64>,
+}
+
+/// Every session with its registrations counted by state, unconfirmed ones included
+pub async fn load_summary(
+    conn: &mut AsyncMysqlConnection,
+    now: NaiveDateTime,
+) -> Result<Vec<SessionSummary>, diesel::result::Error> {
+    let all_sessions = sessions::table
+        .order((
+            sessions::turnus.asc(),
+            sessions::session_date.asc(),
+            sessions::start_time.asc(),
+            sessions::field_code.asc(),
+        ))
+        .load::<Session>(conn)
+        .await?;
+
+    let counts = registrations::table
+        .group_by((
+            registrations::session_id,
+            registrations::confirmed,
+            registrations::waitlisted,
+        ))
+        .select((
+            registrations::session_id,
+            registrations::confirmed,
+            registrations::waitlisted,
+            diesel::dsl::count_star(),
+            diesel::dsl::count(registrations::checked_in_at),
+        ))
+        .load::<(i32, bool, bool, i64, i64)>(conn)
+        .await?;
+
+    let mut by_session: HashMap<i32, (i64, i64, i64, i64)> = HashMap::new();
+    for (session_id, confirmed, waitlisted, count, checked_in) in counts {
+        let entry = by_session.entry(session_id).or_default();
+        if confirmed {
+            entry.0 += count;
+            entry.3 += checked_in;
+        } else if waitlisted {
+            entry.2 += count;
+        } else {
+            entry.1 += count;
+        }
+    }
+
+    Ok(all_sessions
+        .into_iter()
+        .map(|session| {
+            let (confirmed, pending, waitlisted, checked_in) =
+                by_session.get(&session.id).copied().unwrap_or_default();
+            let ended = session.session_date.and_time(session.end_time) <= now;
+            SessionSummary {
+                session,
+                confirmed,
+                pending,
+                waitlisted,
+                checked_in,
+                no_shows: ended.then_some(confirmed - checked_in),
+            }
+        })
+        .collect())
+}
+
+/// Cell formats shared by every sheet of the workbook
+struct Formats {
+    header: Format,
+    date: Format,
+    time: Format,
+    datetime: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Self {
+            header: Format::new().set_bold(),
+            date: Format::new().set_num_format("dd.mm.yyyy"),
+            time: Format::new().set_num_format("hh:mm"),
+            datetime: Format::new().set_num_format("dd.mm.yyyy hh:mm:ss"),
+        }
+    }
+}
+
+// Name of the summary sheet closing every workbook
+const SUMMARY_SHEET: &str = "Prehľad";
+const SHEET_NAME_MAX_CHARS: usize = 31;
+
+/// Worksheet names may not contain `[]:*?/\`, be empty or start or end with `'`
+fn sheet_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .collect();
+    let name = name.trim().trim_matches('\'');
+    if name.is_empty() {
+        "Bez kódu".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Valid sheet names for `names`, cut to 31 characters and distinct the way Excel
+/// compares them, ignoring case. Repeats get a " (2)", " (3)", ... suffix.
+fn unique_sheet_names(names: Vec<String>, reserved: &[&str]) -> Vec<String> {
+    let mut taken: HashSet<String> = reserved.iter().map(|name| name.to_lowercase()).collect();
+    names
+        .iter()
+        .map(|name| {
+            let base = sheet_name(name);
+            let shortened = |max_chars: usize| -> String {
+                let cut: String = base.chars().take(max_chars).collect();
+                cut.trim_end_matches('\'').to_string()
+            };
+
+            let mut candidate = shortened(SHEET_NAME_MAX_CHARS);
+            let mut copy = 2;
+            while !taken.insert(candidate.to_lowercase()) {
+                let suffix = format!(" ({})", copy);
+                candidate = shortened(SHEET_NAME_MAX_CHARS - suffix.chars().count()) + &suffix;
+                copy += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Records split into named worksheets, in sheet order
+fn group_records<'r, 'a>(
+    records: &'r [ExportRecord<'a>],
+    grouping: SheetGrouping,
+) -> Vec<(String, Vec<&'r ExportRecord<'a>>)> {
+    match grouping {
+        SheetGrouping::None => vec![("Registrácie".to_string(), records.iter().collect())],
+        SheetGrouping::Turnus => {
+            let mut groups: BTreeMap<i32, Vec<_>> = BTreeMap::new();
+            for record in records {
+                groups.entry(record.turnus).or_default().push(record);
+            }
+            groups
+                .into_iter()
+                .map(|(turnus, rows)| (format!("Turnus {}", turnus), rows))
+                .collect()
+        }
+        SheetGrouping::Field => {
+            let mut groups: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+            for record in records {
+                groups.entry(record.field_code).or_default().push(record);
+            }
+            groups
+                .into_iter()
+                .map(|(field_code, rows)| (field_code.to_string(), rows))
+                .collect()
+        }
+    }
+}
+
+fn write_records(
+    worksheet: &mut Worksheet,
+    records: &[&ExportRecord],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    for (col, header) in HEADERS.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &formats.header)?;
+    }
+
+    for (i, record) in records.iter().enumerate() {
+        let row = (i + 1) as u32;
+        worksheet.write_number(row, 0, record.id as f64)?;
+        worksheet.write_string(row, 1, record.student_first_name)?;
+        worksheet.write_string(row, 2, record.student_last_name)?;
+        worksheet.write_string(row, 3, record.guardian_first_name)?;
+        worksheet.write_string(row, 4, record.guardian_last_name)?;
+        worksheet.write_string(row, 5, record.guardian_email)?;
+        worksheet.write_string(row, 6, record.guardian_phone)?;
+        worksheet.write_number(row, 7, record.turnus as f64)?;
+        worksheet.write_date_with_format(row, 8, record.session_date, &formats.date)?;
+        worksheet.write_time_with_format(row, 9, record.start_time, &formats.time)?;
+        worksheet.write_time_with_format(row, 10, record.end_time, &formats.time)?;
+        worksheet.write_string(row, 11, record.field_code)?;
+        worksheet.write_string(row, 12, record.field_name)?;
+        worksheet.write_string(row, 13, record.status.label())?;
+        if let Some(created_at) = record.created_at {
+            worksheet.write_datetime_with_format(row, 14, created_at, &formats.datetime)?;
+        }
+        if let Some(position) = record.waitlist_position {
+            worksheet.write_number(row, 15, position as f64)?;
+        }
+        if let Some(checked_in_at) = record.checked_in_at {
+            worksheet.write_datetime_with_format(row, 16, checked_in_at, &formats.datetime)?;
+        }
+    }
+
+    worksheet.autofit();
+    // Add autofilter to all columns
+    worksheet.autofilter(0, 0, records.len() as u32, (HEADERS.len() - 1) as u16)?;
+    Ok(())
+}
+
+const SUMMARY_HEADERS: [&str; 13] = [
+    "Turnus",
+    "Dátum",
+    "Začiatok",
+    "Koniec",
+    "Odbor",
+    "Názov odboru",
+    "Kapacita",
+    "Potvrdené",
+    "Nepotvrdené",
+    "Náhradníci",
+    "Voľné miesta",
+    "Prítomní",
+    "Neprišli",
+];
+
+fn write_summary(
+    worksheet: &mut Worksheet,
+    summary: &[SessionSummary],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    for (col, header) in SUMMARY_HEADERS.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &formats.header)?;
+    }
+
+    let mut totals = [0i64; 7];
+    for (i, entry) in summary.iter().enumerate() {
+        let row = (i + 1) as u32;
+        let session = &entry.session;
+        let capacity = i64::from(session.max_capacity);
+        let counts = [
+            Some(capacity),
+            Some(entry.confirmed),
+            Some(entry.pending),
+            Some(entry.waitlisted),
+            Some((capacity - entry.confirmed).max(0)),
+            Some(entry.checked_in),
+            entry.no_shows,
+        ];
+
+        worksheet.write_number(row, 0, session.turnus as f64)?;
+        worksheet.write_date_with_format(row, 1, session.session_date, &formats.date)?;
+        worksheet.write_time_with_format(row, 2, session.start_time, &formats.time)?;
+        worksheet.write_time_with_format(row, 3, session.end_time, &formats.time)?;
+        worksheet.write_string(row, 4, &session.field_code)?;
+        worksheet.write_string(row, 5, &session.field_name)?;
+        for (offset, count) in counts.iter().enumerate() {
+            // Sessions still to come have no no-shows yet, their cell stays empty
+            if let Some(count) = count {
+                worksheet.write_number(row, 6 + offset as u16, *count as f64)?;
+                totals[offset] += count;
+            }
+        }
+    }
+
+    let total_row = (summary.len() + 1) as u32;
+    worksheet.write_string_with_format(total_row, 0, "Spolu", &formats.header)?;
+    for (offset, total) in totals.iter().enumerate() {
+        worksheet.write_number_with_format(
+            total_row,
+            6 + offset as u16,
+            *total as f64,
+            &formats.header,
+        )?;
+    }
+
+    worksheet.autofit();
+    Ok(())
+}
+
+/// Workbook with the registrations split per `grouping`, followed by a summary sheet
+pub fn xlsx(
+    records: &[ExportRecord],
+    grouping: SheetGrouping,
+    summary: &[SessionSummary],
+) -> Result<Vec<u8>, XlsxError> {
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+
+    let (names, groups): (Vec<_>, Vec<_>) = group_records(records, grouping).into_iter().unzip();
+    for (name, rows) in unique_sheet_names(names, &[SUMMARY_SHEET])
+        .into_iter()
+        .zip(groups)
+    {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(name)?;
+        write_records(worksheet, &rows, &formats)?;
+    }
+
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(SUMMARY_SHEET)?;
+    write_summary(worksheet, summary, &formats)?;
+
+    workbook.save_to_buffer()
+}
+
+/// Whether a cell is a plain signed number such as `+421 900 000 000`, which
+/// spreadsheet apps read as a value and never as a formula
+fn is_plain_number(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['+', '-']).unwrap_or(cell);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == ' ')
+}
+
+/// Quote a cell spreadsheet apps would run as a formula, e.g. a name starting with `=`
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(['=', '+', '-', '@', '\t', '\r']) && !is_plain_number(&cell) {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+pub fn csv(records: &[ExportRecord]) -> Result<Vec<u8>, csv::Error> {
+    // The BOM tells Excel the file is UTF-8, otherwise diacritics break
+    let mut buf = "\u{feff}".as_bytes().to_vec();
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b';')
+            .terminator(csv::Terminator::CRLF)
+            .from_writer(&mut buf);
+
+        writer.write_record(HEADERS)?;
+        for record in records {
+            writer.write_record(record.texts().map(escape_formula))?;
+        }
+        writer.flush()?;
+    }
+    Ok(buf)
+}
+
+pub fn jsonl(records: &[ExportRecord]) -> Result<Vec<u8>, serde_json::Error> {
+    let mut buf = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut buf, record)?;
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique(names: &[&str]) -> Vec<String> {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        unique_sheet_names(names, &[SUMMARY_SHEET])
+    }
+
+    #[test]
+    fn sheet_names_differing_in_case_get_a_suffix() {
+        assert_eq!(unique(&["IT", "it", "It"]), ["IT", "it (2)", "It (3)"]);
+        assert_eq!(unique(&["PREHĽAD"]), ["PREHĽAD (2)"]);
+    }
+
+    #[test]
+    fn truncated_sheet_names_stay_distinct() {
+        let long = "Elektrotechnika a informačné technológie";
+        let names = unique(&[&format!("{} A", long), &format!("{} B", long)]);
+
+        assert_eq!(names[0], "Elektrotechnika a informačné te");
+        assert_eq!(names[1], "Elektrotechnika a informačn (2)");
+        assert!(names.iter().all(|name| name.chars().count() <= 31));
+    }
+
+    #[test]
+    fn empty_and_invalid_sheet_names_are_replaced() {
+        assert_eq!(
+            unique(&["", "  ", "a/b", "'x'"]),
+            ["Bez kódu", "Bez kódu (2)", "a_b", "x"]
+        );
+    }
+
+    #[test]
+    fn workbook_with_colliding_field_codes_is_written() {
+        let names = unique(&["el", "EL", "", "[]"]);
+        let mut workbook = Workbook::new();
+        for name in names {
+            workbook.add_worksheet().set_name(name).unwrap();
+        }
+        workbook.add_worksheet().set_name(SUMMARY_SHEET).unwrap();
+        assert!(workbook.save_to_buffer().is_ok());
+    }
+
+    #[test]
+    fn formula_cells_are_quoted() {
+        for cell in ["=1+1", "+1+1", "-2-cmd", "+ 1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(escape_formula(cell.to_string()), format!("'{}", cell));
+        }
+        for cell in [
+            "Ján",
+            "",
+            "1+1",
+            "a=b",
+            "+421900000000",
+            "+421 900 000 000",
+            "-2",
+        ] {
+            assert_eq!(escape_formula(cell.to_string()), cell);
+        }
+    }
+}
@@ -0,0 +1,692 @@
+// Import of sessions from an uploaded .xlsx or CSV sheet: parsing, the diff
+// against the sessions table and writing it
+
+use calamine::{Data, DataType, Reader, Xlsx};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rocket::serde::json::json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Cursor;
+
+use crate::booking::{BookingError, Promotion, occupies_seat, promote_waitlist};
+use crate::error::ApiError;
+use crate::models::{
+    ImportAction, NewSession, Session, SessionImportReport, SessionImportRow, SessionRequest,
+};
+use crate::schema::{registrations, sessions};
+use crate::validation::validate_session;
+
+// Accepted header names per session column, compared case-insensitively.
+// The Slovak names match the summary sheet of the Excel export.
+const COLUMNS: [(&str, &[&str]); 7] = [
+    ("field_code", &["field_code", "odbor", "kód odboru"]),
+    ("field_name", &["field_name", "názov odboru"]),
+    ("session_date", &["session_date", "dátum"]),
+    ("start_time", &["start_time", "začiatok"]),
+    ("end_time", &["end_time", "koniec"]),
+    ("max_capacity", &["max_capacity", "kapacita"]),
+    ("turnus", &["turnus"]),
+];
+
+const DATE_FORMATS: [&str; 2] = ["%d.%m.%Y", "%Y-%m-%d"];
+const TIME_FORMATS: [&str; 2] = ["%H:%M", "%H:%M:%S"];
+
+/// Cell of either file type
+enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+    DateTime(NaiveDateTime),
+}
+
+impl From<&Data> for Cell {
+    fn from(data: &Data) -> Self {
+        match data {
+            Data::Empty => Cell::Empty,
+            Data::Int(n) => Cell::Number(*n as f64),
+            Data::Float(n) => Cell::Number(*n),
+            Data::DateTime(_) | Data::DateTimeIso(_) => match data.as_datetime() {
+                Some(datetime) => Cell::DateTime(datetime),
+                None => Cell::Text(data.to_string()),
+            },
+            other => Cell::Text(other.to_string()),
+        }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        if text.trim().is_empty() {
+            Cell::Empty
+        } else {
+            Cell::Text(text.to_string())
+        }
+    }
+}
+
+impl Cell {
+    fn is_empty(&self) -> bool {
+        matches!(self, Cell::Empty)
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(text) => text.trim().to_string(),
+            Cell::Number(n) => n.to_string(),
+            Cell::DateTime(datetime) => datetime.to_string(),
+        }
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        match self {
+            Cell::DateTime(datetime) => Some(datetime.date()),
+            Cell::Text(text) => {
+                let text = text.replace(' ', "");
+                DATE_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(&text, format).ok())
+            }
+            _ => None,
+        }
+    }
+
+    fn time(&self) -> Option<NaiveTime> {
+        match self {
+            Cell::DateTime(datetime) => Some(datetime.time()),
+            Cell::Text(text) => TIME_FORMATS
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(text.trim(), format).ok()),
+            _ => None,
+        }
+    }
+
+    fn integer(&self) -> Option<i32> {
+        match self {
+            Cell::Number(n) if n.fract() == 0.0 => i32::try_from(*n as i64).ok(),
+            Cell::Text(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Sheet rows with their row numbers, the header first
+type Grid = Vec<(usize, Vec<Cell>)>;
+
+/// One data row of the upload, validated
+#[derive(Debug)]
+pub struct ParsedRow {
+    pub row: usize,
+    pub session: Result<NewSession, BTreeMap<String, &'static str>>,
+}
+
+/// Position of every session column in the header, or the names of the missing ones
+fn map_header(header: &[Cell]) -> Result<[usize; 7], Vec<&'static str>> {
+    let names: Vec<String> = header
+        .iter()
+        .map(|cell| cell.text().to_lowercase())
+        .collect();
+    let mut positions = [0; 7];
+    let mut missing = Vec::new();
+
+    for (i, (column, aliases)) in COLUMNS.iter().enumerate() {
+        match names
+            .iter()
+            .position(|name| aliases.contains(&name.as_str()))
+        {
+            Some(position) => positions[i] = position,
+            None => missing.push(*column),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(positions)
+    } else {
+        Err(missing)
+    }
+}
+
+fn xlsx_grids(bytes: &[u8]) -> Result<Vec<Grid>, ApiError> {
+    let mut workbook: Xlsx<_> = Xlsx::new(Cursor::new(bytes))
+        .map_err(|_| ApiError::unprocessable("invalid_file", "Súbor sa nedá prečítať"))?;
+
+    Ok(workbook
+        .worksheets()
+        .into_iter()
+        .map(|(_, range)| {
+            let first_row = range.start().map_or(0, |(row, _)| row as usize);
+            range
+                .rows()
+                .enumerate()
+                .map(|(i, cells)| (first_row + i + 1, cells.iter().map(Cell::from).collect()))
+                .collect()
+        })
+        .collect())
+}
+
+fn csv_grid(bytes: &[u8]) -> Result<Grid, ApiError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ApiError::unprocessable("invalid_file", "Súbor musí byť v kódovaní UTF-8"))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    // Slovak Excel saves CSV with semicolons, most other tools use commas
+    let header_line = text.lines().next().unwrap_or_default();
+    let delimiter = if header_line.matches(';').count() >= header_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            let record = record
+                .map_err(|_| ApiError::unprocessable("invalid_file", "Súbor sa nedá prečítať"))?;
+            let row = record.position().map_or(i + 1, |p| p.line() as usize);
+            Ok((row, record.iter().map(Cell::from).collect()))
+        })
+        .collect()
+}
+
+/// Record an error for a cell that did not parse, `required` when it is empty
+fn required<T>(
+    errors: &mut BTreeMap<String, &'static str>,
+    column: usize,
+    cell: &Cell,
+    value: Option<T>,
+    code: &'static str,
+) -> Option<T> {
+    if value.is_none() {
+        let code = if cell.is_empty() { "required" } else { code };
+        errors.insert(COLUMNS[column].0.to_string(), code);
+    }
+    value
+}
+
+/// Turn one sheet row into a session, `None` for rows without session data
+fn read_row(row: usize, cells: &[Cell], columns: &[usize; 7]) -> Option<ParsedRow> {
+    const EMPTY: Cell = Cell::Empty;
+    let cell = |i: usize| cells.get(columns[i]).unwrap_or(&EMPTY);
+
+    // Blank lines and totals rows carry nothing but numbers
+    if (0..5).all(|i| cell(i).is_empty()) {
+        return None;
+    }
+
+    let mut errors = BTreeMap::new();
+    let session_date = required(&mut errors, 2, cell(2), cell(2).date(), "invalid_date");
+    let start_time = required(&mut errors, 3, cell(3), cell(3).time(), "invalid_time");
+    let end_time = required(&mut errors, 4, cell(4), cell(4).time(), "invalid_time");
+    let max_capacity = required(&mut errors, 5, cell(5), cell(5).integer(), "invalid_number");
+    let turnus = required(&mut errors, 6, cell(6), cell(6).integer(), "invalid_number");
+
+    let session = match (session_date, start_time, end_time, max_capacity, turnus) {
+        (
+            Some(session_date),
+            Some(start_time),
+            Some(end_time),
+            Some(max_capacity),
+            Some(turnus),
+        ) => validate_session(&SessionRequest {
+            field_code: cell(0).text(),
+            field_name: cell(1).text(),
+            session_date,
+            start_time,
+            end_time,
+            max_capacity,
+            turnus,
+        })
+        .map_err(|e| e.into_fields()),
+        _ => Err(errors),
+    };
+
+    Some(ParsedRow { row, session })
+}
+
+/// Parse an uploaded .xlsx or CSV file. Workbooks are read from the first sheet
+/// that has every session column.
+pub fn parse(bytes: &[u8]) -> Result<Vec<ParsedRow>, ApiError> {
+    // .xlsx files are zip archives
+    let grids = if bytes.starts_with(b"PK\x03\x04") {
+        xlsx_grids(bytes)?
+    } else {
+        vec![csv_grid(bytes)?]
+    };
+
+    let mut first_missing = None;
+    for grid in grids {
+        let Some(((_, header), data)) = grid.split_first() else {
+            continue;
+        };
+        match map_header(header) {
+            Ok(columns) => {
+                return Ok(data
+                    .iter()
+                    .filter_map(|(row, cells)| read_row(*row, cells, &columns))
+                    .collect());
+            }
+            Err(missing) => {
+                first_missing.get_or_insert(missing);
+            }
+        }
+    }
+
+    Err(match first_missing {
+        Some(missing) => ApiError::unprocessable(
+            "missing_columns",
+            "V súbore chýbajú niektoré stĺpce termínov",
+        )
+        .with_details(json!({ "columns": missing })),
+        None => ApiError::unprocessable("empty_file", "Súbor neobsahuje žiadne údaje"),
+    })
+}
+
+/// Sessions are matched by field code, date and start time
+type SessionKey = (String, NaiveDate, NaiveTime);
+
+fn key(field_code: &str, session_date: NaiveDate, start_time: NaiveTime) -> SessionKey {
+    (field_code.to_string(), session_date, start_time)
+}
+
+/// Fields of an existing session the import would change
+fn changed_fields(existing: &Session, new: &NewSession) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+    if existing.field_name != new.field_name {
+        changes.push("field_name");
+    }
+    if existing.end_time != new.end_time {
+        changes.push("end_time");
+    }
+    if existing.max_capacity != new.max_capacity {
+        changes.push("max_capacity");
+    }
+    if existing.turnus != new.turnus {
+        changes.push("turnus");
+    }
+    changes
+}
+
+/// Compare the parsed rows with the sessions table. Locks the session rows, so
+/// inside a transaction nothing can be booked until it is applied.
+pub async fn plan(
+    conn: &mut AsyncMysqlConnection,
+    rows: Vec<ParsedRow>,
+    dry_run: bool,
+) -> Result<SessionImportReport, diesel::result::Error> {
+    let existing = sessions::table
+        .order(sessions::id.asc())
+        .for_update()
+        .load::<Session>(conn)
+        .await?;
+
+    let occupied: HashMap<i32, i64> = registrations::table
+        .filter(occupies_seat())
+        .group_by(registrations::session_id)
+        .select((registrations::session_id, diesel::dsl::count_star()))
+        .load::<(i32, i64)>(conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(diff(existing, &occupied, rows, dry_run))
+}
+
+/// Compare the parsed rows with the `existing` sessions, `taken` holds the
+/// occupied seats per session id
+fn diff(
+    existing: Vec<Session>,
+    taken: &HashMap<i32, i64>,
+    rows: Vec<ParsedRow>,
+    dry_run: bool,
+) -> SessionImportReport {
+    let mut by_key: HashMap<SessionKey, &Session> = HashMap::new();
+    for session in existing.iter().rev() {
+        by_key.insert(
+            key(
+                &session.field_code,
+                session.session_date,
+                session.start_time,
+            ),
+            session,
+        );
+    }
+
+    let mut seen = HashSet::new();
+    let mut matched = HashSet::new();
+    let mut report_rows = Vec::new();
+
+    for parsed in rows {
+        let mut row = SessionImportRow {
+            row: parsed.row,
+            action: ImportAction::Invalid,
+            session_id: None,
+            session: None,
+            changes: Vec::new(),
+            errors: BTreeMap::new(),
+        };
+
+        let new = match parsed.session {
+            Ok(new) => new,
+            Err(errors) => {
+                row.errors = errors;
+                report_rows.push(row);
+                continue;
+            }
+        };
+
+        let new_key = key(&new.field_code, new.session_date, new.start_time);
+        if !seen.insert(new_key.clone()) {
+            row.errors.insert("start_time".to_string(), "duplicate_row");
+        } else if let Some(session) = by_key.get(&new_key) {
+            matched.insert(session.id);
+            row.session_id = Some(session.id);
+            row.changes = changed_fields(session, &new);
+
+            // Capacity must not drop below the seats already taken
+            let taken = taken.get(&session.id).copied().unwrap_or(0);
+            if (new.max_capacity as i64) < taken {
+                row.errors
+                    .insert("max_capacity".to_string(), "below_registrations");
+            } else if row.changes.is_empty() {
+                row.action = ImportAction::Unchanged;
+            } else {
+                row.action = ImportAction::Update;
+            }
+        } else {
+            row.action = ImportAction::Create;
+        }
+
+        row.session = Some(new);
+        report_rows.push(row);
+    }
+
+    let count = |action| report_rows.iter().filter(|r| r.action == action).count();
+    SessionImportReport {
+        dry_run,
+        created: count(ImportAction::Create),
+        updated: count(ImportAction::Update),
+        unchanged: count(ImportAction::Unchanged),
+        invalid: count(ImportAction::Invalid),
+        untouched: existing
+            .into_iter()
+            .filter(|session| !matched.contains(&session.id))
+            .collect(),
+        rows: report_rows,
+    }
+}
+
+/// Write a planned import. Returns the ids of updated sessions and of
+/// registrations promoted from the waitlist by a higher capacity.
+pub async fn apply(
+    conn: &mut AsyncMysqlConnection,
+    report: &SessionImportReport,
+    promotion: Promotion,
+) -> Result<(Vec<i32>, Vec<i32>), BookingError> {
+    let mut updated = Vec::new();
+    let mut promoted = Vec::new();
+
+    for row in &report.rows {
+        let Some(session) = &row.session else {
+            continue;
+        };
+        match (row.action, row.session_id) {
+            (ImportAction::Create, _) => {
+                diesel::insert_into(sessions::table)
+                    .values(session)
+                    .execute(conn)
+                    .await?;
+            }
+            (ImportAction::Update, Some(id)) => {
+                diesel::update(sessions::table.find(id))
+                    .set(session)
+                    .execute(conn)
+                    .await?;
+                // Seats added by a higher capacity go to the waitlist first
+                promoted.extend(promote_waitlist(conn, id, promotion).await?);
+                updated.push(id);
+            }
+            _ => {}
+        }
+    }
+
+    Ok((updated, promoted))
+}
+
+/// Failure of a committed import
+pub enum ImportError {
+    /// Some rows are invalid, nothing was written
+    Invalid(SessionImportReport),
+    Booking(BookingError),
+}
+
+impl From<diesel::result::Error> for ImportError {
+    fn from(e: diesel::result::Error) -> Self {
+        ImportError::Booking(e.into())
+    }
+}
+
+impl From<BookingError> for ImportError {
+    fn from(e: BookingError) -> Self {
+        ImportError::Booking(e)
+    }
+}
+
+impl From<ImportError> for ApiError {
+    fn from(e: ImportError) -> Self {
+        match e {
+            ImportError::Invalid(report) => ApiError::unprocessable(
+                "import_invalid",
+                "Súbor obsahuje chybné riadky, nič nebolo uložené",
+            )
+            .with_details(json!(report)),
+            ImportError::Booking(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_xlsxwriter::{Format, Workbook};
+
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn session(id: i32, field_code: &str, max_capacity: i32) -> Session {
+        Session {
+            id,
+            field_code: field_code.to_string(),
+            field_name: "Informatika".to_string(),
+            session_date: date(10),
+            start_time: time(8),
+            end_time: time(10),
+            max_capacity,
+            turnus: 1,
+        }
+    }
+
+    fn row(row: usize, field_code: &str, max_capacity: i32) -> ParsedRow {
+        ParsedRow {
+            row,
+            session: Ok(NewSession {
+                field_code: field_code.to_string(),
+                field_name: "Informatika".to_string(),
+                session_date: date(10),
+                start_time: time(8),
+                end_time: time(10),
+                max_capacity,
+                turnus: 1,
+            }),
+        }
+    }
+
+    fn sessions(rows: &[ParsedRow]) -> Vec<&NewSession> {
+        rows.iter()
+            .map(|row| row.session.as_ref().expect("Row did not parse"))
+            .collect()
+    }
+
+    #[test]
+    fn semicolon_csv_with_slovak_headers() {
+        let file = "\u{feff}Odbor;Názov odboru;Dátum;Začiatok;Koniec;Kapacita;Turnus\n\
+                    inf;Informatika;10. 3. 2026;8:00;10:00;20;1\n\
+                    ;;;;;20;\n";
+        let rows = parse(file.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].row, 2);
+        let session = sessions(&rows)[0];
+        assert_eq!(session.field_code, "INF");
+        assert_eq!(session.session_date, date(10));
+        assert_eq!((session.start_time, session.end_time), (time(8), time(10)));
+        assert_eq!((session.max_capacity, session.turnus), (20, 1));
+    }
+
+    #[test]
+    fn comma_csv_columns_are_found_by_name() {
+        let file = "turnus,max_capacity,end_time,start_time,session_date,field_name,field_code\n\
+                    2,15,12:00:00,10:00,2026-03-11,Elektrotechnika,EL\n";
+        let rows = parse(file.as_bytes()).unwrap();
+
+        let session = sessions(&rows)[0];
+        assert_eq!(session.field_code, "EL");
+        assert_eq!(session.session_date, date(11));
+        assert_eq!((session.start_time, session.end_time), (time(10), time(12)));
+        assert_eq!((session.max_capacity, session.turnus), (15, 2));
+    }
+
+    #[test]
+    fn unreadable_cells_are_reported_per_column() {
+        let file = "Odbor;Názov odboru;Dátum;Začiatok;Koniec;Kapacita;Turnus\n\
+                    INF;Informatika;31.02.2026;8h;10:00;;x\n";
+        let rows = parse(file.as_bytes()).unwrap();
+
+        let errors = rows[0].session.as_ref().unwrap_err();
+        assert_eq!(errors.get("session_date"), Some(&"invalid_date"));
+        assert_eq!(errors.get("start_time"), Some(&"invalid_time"));
+        assert_eq!(errors.get("max_capacity"), Some(&"required"));
+        assert_eq!(errors.get("turnus"), Some(&"invalid_number"));
+        assert_eq!(errors.get("end_time"), None);
+    }
+
+    #[test]
+    fn missing_columns_are_named() {
+        let header: Vec<Cell> = ["Odbor", "Dátum", "Začiatok", "Koniec", "Kapacita"]
+            .into_iter()
+            .map(Cell::from)
+            .collect();
+        assert_eq!(
+            map_header(&header).unwrap_err(),
+            vec!["field_name", "turnus"]
+        );
+
+        let error = parse("Odbor;Dátum\nINF;10.03.2026\n".as_bytes()).unwrap_err();
+        assert!(format!("{:?}", error).contains("\"missing_columns\""));
+    }
+
+    #[test]
+    fn xlsx_rows_are_read_from_the_sheet_with_sessions() {
+        let mut workbook = Workbook::new();
+        workbook
+            .add_worksheet()
+            .write_string(0, 0, "Poznámky")
+            .unwrap();
+
+        let sheet = workbook.add_worksheet();
+        for (col, header) in COLUMNS.iter().map(|(name, _)| name).enumerate() {
+            sheet.write_string(0, col as u16, *header).unwrap();
+        }
+        let date_format = Format::new().set_num_format("dd.mm.yyyy");
+        let time_format = Format::new().set_num_format("hh:mm");
+        sheet.write_string(1, 0, "INF").unwrap();
+        sheet.write_string(1, 1, "Informatika").unwrap();
+        sheet
+            .write_date_with_format(1, 2, date(12), &date_format)
+            .unwrap();
+        sheet
+            .write_time_with_format(1, 3, time(8), &time_format)
+            .unwrap();
+        sheet.write_string(1, 4, "10:00").unwrap();
+        sheet.write_number(1, 5, 25.0).unwrap();
+        sheet.write_number(1, 6, 2.0).unwrap();
+        let bytes = workbook.save_to_buffer().unwrap();
+
+        let rows = parse(&bytes).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].row, 2);
+        let session = sessions(&rows)[0];
+        assert_eq!(session.session_date, date(12));
+        assert_eq!((session.start_time, session.end_time), (time(8), time(10)));
+        assert_eq!((session.max_capacity, session.turnus), (25, 2));
+    }
+
+    #[test]
+    fn dry_run_diff_sorts_rows_by_action() {
+        let existing = vec![
+            session(1, "INF", 20),
+            session(2, "EL", 20),
+            session(3, "STR", 20),
+            session(4, "AUT", 20),
+        ];
+        let taken = HashMap::from([(3, 12)]);
+        let rows = vec![
+            row(2, "INF", 20),
+            row(3, "EL", 30),
+            row(4, "STR", 10),
+            row(5, "CHEM", 20),
+            row(6, "CHEM", 20),
+        ];
+
+        let report = diff(existing, &taken, rows, true);
+        let actions: Vec<_> = report
+            .rows
+            .iter()
+            .map(|row| (row.action, row.session_id))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (ImportAction::Unchanged, Some(1)),
+                (ImportAction::Update, Some(2)),
+                (ImportAction::Invalid, Some(3)),
+                (ImportAction::Create, None),
+                (ImportAction::Invalid, None),
+            ]
+        );
+        assert_eq!(report.rows[1].changes, vec!["max_capacity"]);
+        assert_eq!(
+            report.rows[2].errors.get("max_capacity"),
+            Some(&"below_registrations")
+        );
+        assert_eq!(
+            report.rows[4].errors.get("start_time"),
+            Some(&"duplicate_row")
+        );
+        assert_eq!(
+            (
+                report.created,
+                report.updated,
+                report.unchanged,
+                report.invalid
+            ),
+            (1, 1, 1, 2)
+        );
+        assert!(report.dry_run);
+        let untouched: Vec<_> = report.untouched.iter().map(|s| s.id).collect();
+        assert_eq!(untouched, vec![4]);
+    }
+}
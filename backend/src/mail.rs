@@ -0,0 +1,94 @@
+// Outgoing mail with a pluggable transport (SMTP, .eml files or the log)
+
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{AppConfig, MailTransportKind};
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    File(AsyncFileTransport<Tokio1Executor>),
+    Log,
+}
+
+/// Mail sender managed as Rocket state
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Transport,
+    from: Mailbox,
+}
+
+impl Mailer {
+    /// Build the configured transport, failing on settings it cannot work with
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let from = config
+            .mail_from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid mail_from address {}: {}", config.mail_from, e))?;
+
+        let transport = match config.mail_transport {
+            MailTransportKind::Smtp => {
+                let host = config
+                    .smtp_host
+                    .as_deref()
+                    .ok_or("smtp_host must be set when mail_transport = \"smtp\"")?;
+                let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                    .map_err(|e| format!("Invalid SMTP relay {}: {}", host, e))?
+                    .port(config.smtp_port);
+                if let (Some(username), Some(password)) =
+                    (&config.smtp_username, &config.smtp_password)
+                {
+                    builder =
+                        builder.credentials(Credentials::new(username.clone(), password.clone()));
+                }
+                Transport::Smtp(builder.build())
+            }
+            MailTransportKind::File => {
+                std::fs::create_dir_all(&config.mail_dir)
+                    .map_err(|e| format!("Failed to create mail_dir {}: {}", config.mail_dir, e))?;
+                Transport::File(AsyncFileTransport::<Tokio1Executor>::new(&config.mail_dir))
+            }
+            MailTransportKind::Log => Transport::Log,
+        };
+
+        Ok(Mailer { transport, from })
+    }
+
+    /// Send a plain text email
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        let to = to
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid recipient {}: {}", to, e))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|e| format!("Failed to build email: {}", e))?;
+
+        match &self.transport {
+            Transport::Smtp(smtp) => smtp
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("SMTP error: {}", e)),
+            Transport::File(file) => file
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Failed to write email: {}", e)),
+            Transport::Log => {
+                println!(
+                    "📧 Email\n{}",
+                    String::from_utf8_lossy(&message.formatted())
+                );
+                Ok(())
+            }
+        }
+    }
+}
@@ -3,10 +3,31 @@
 #[macro_use]
 extern crate rocket;
 
+mod attendance;
+mod audit;
+mod availability;
+mod auth;
+mod booking;
+mod checkin;
+mod clock;
 mod db;
+mod error;
+mod events;
+mod export;
+mod import;
+mod mail;
+mod maintenance;
 mod models;
+mod notify;
+mod rate_limit;
 mod routes;
 mod schema;
+mod spam;
+#[cfg(test)]
+mod test_db;
+mod totp;
+mod validation;
+mod windows;
 mod config;
 
 use config::AppConfig;
@@ -14,22 +35,61 @@ use diesel::prelude::*;
 use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket_db_pools::Database;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use tokio::sync::broadcast;
 
+use availability::SessionCache;
+use clock::Clock;
 use db::RegistrationDB;
+use events::LiveEvent;
+use mail::Mailer;
+use rate_limit::{LoginThrottle, WindowCounter};
 use routes::registration;
+use spam::{FormTokens, SpamStats};
 
 pub struct AppState {
     pub registration_enabled: AtomicBool,
-    pub tx: broadcast::Sender<bool>,
-    pub admin_password_hash: String,
+    pub tx: broadcast::Sender<LiveEvent>,
+    pub login_throttle: LoginThrottle,
+    pub register_limiter: WindowCounter<IpAddr>,
+    pub form_tokens: FormTokens,
+    pub spam_stats: SpamStats,
+    /// Time used for booking rules, fixed in tests
+    pub clock: Clock,
+    /// Session listing with seat counts, see `availability::SessionCache`
+    pub session_cache: Arc<SessionCache>,
 }
 
 async fn load_initial_state(
     rocket: rocket::Rocket<rocket::Build>,
 ) -> rocket::Rocket<rocket::Build> {
     let config = rocket.state::<AppConfig>().expect("AppConfig not managed").clone();
+    let login_throttle =
+        LoginThrottle::new(config.login_max_attempts, config.login_global_max_failures);
+    let register_limiter =
+        WindowCounter::new(config.register_max_per_ip, Duration::from_secs(60 * 60));
+    let form_tokens = FormTokens::new(
+        config.form_token_secret.as_deref(),
+        config.register_min_fill_seconds,
+    );
+    let session_cache = Arc::new(SessionCache::new(Duration::from_secs(
+        config.sessions_cache_seconds,
+    )));
+
+    // Attendance sheets need a TrueType font for letters such as č, ľ or ť
+    match &config.pdf_font_path {
+        None => eprintln!(
+            "⚠️  pdf_font_path is not set, attendance sheets print Slovak names without some diacritics"
+        ),
+        Some(path) if !std::path::Path::new(path).is_file() => eprintln!(
+            "⚠️  pdf_font_path {} is not a file, printing attendance sheets will fail",
+            path
+        ),
+        Some(_) => {}
+    }
 
     let enabled = rocket::tokio::task::spawn_blocking(move || {
         let mut conn = diesel::MysqlConnection::establish(&config.database_url)
@@ -49,20 +109,39 @@ async fn load_initial_state(
 
     let (tx, _) = broadcast::channel(100);
 
-    let admin_password_hash = config.admin_password_hash.clone();
-
     rocket.manage(AppState {
         registration_enabled: AtomicBool::new(enabled),
         tx,
-        admin_password_hash,
+        login_throttle,
+        register_limiter,
+        form_tokens,
+        spam_stats: SpamStats::default(),
+        clock: Clock::System,
+        session_cache,
     })
 }
 
+// Helper function to manage the mailer, aborting launch on a bad mail configuration
+async fn setup_mailer(rocket: rocket::Rocket<rocket::Build>) -> rocket::fairing::Result {
+    let config = rocket.state::<AppConfig>().expect("AppConfig not managed");
+    match Mailer::from_config(config) {
+        Ok(mailer) => Ok(rocket.manage(mailer)),
+        Err(e) => {
+            eprintln!("Invalid mail configuration: {}", e);
+            Err(rocket)
+        }
+    }
+}
+
 #[rocket::launch]
 fn rocket() -> rocket::Rocket<rocket::Build> {
     dotenvy::dotenv().ok();
 
-    let config = AppConfig::load();
+    build_rocket(AppConfig::load())
+}
+
+// Helper function to assemble the server from a loaded configuration
+fn build_rocket(config: AppConfig) -> rocket::Rocket<rocket::Build> {
     let mut figment = rocket::config::Config::figment()
         .merge(("port", config.rocket_port))
         .merge(("address", &config.rocket_address));
@@ -81,25 +160,63 @@ fn rocket() -> rocket::Rocket<rocket::Build> {
 
     rocket::custom(figment)
         .manage(config.clone())
+        .attach(AdHoc::try_on_ignite("Mailer", setup_mailer))
         .attach(RegistrationDB::init())
         .attach(AdHoc::on_ignite("Database Migrations", db::run_migrations))
+        .attach(AdHoc::on_ignite("Bootstrap Admin", db::bootstrap_admin))
         .attach(AdHoc::on_ignite("Load Initial State", load_initial_state))
+        .attach(AdHoc::on_liftoff("Background Cleanup", |rocket| {
+            Box::pin(maintenance::spawn_cleanup(rocket))
+        }))
         .mount(
             "/api",
             routes![
                 registration::client::get_sessions,
+                registration::client::get_form_token,
                 registration::client::create_registration,
+                registration::client::create_batch_registration,
                 registration::client::get_registration_status,
+                registration::client::confirm_email_link,
+                registration::client::confirm_email,
+                registration::manage::get_own_registration,
+                registration::manage::cancel_own_registration,
+                registration::manage::move_own_registration,
+                registration::manage::own_checkin_qr,
                 registration::admin::admin_login,
+                registration::admin::admin_login_totp,
                 registration::admin::admin_logout,
                 registration::admin::admin_check,
+                registration::admin::admin_me,
                 registration::admin::get_all_registrations,
                 registration::admin::toggle_registration,
-                registration::admin::export_registrations_excel,
+                registration::admin::export_registrations,
+                registration::attendance::attendance_sheets,
+                registration::checkin::check_in,
                 registration::admin::confirm_registration,
                 registration::admin::delete_registration,
+                registration::admin::get_audit_log,
+                registration::admin::get_spam_stats,
+                registration::sessions::create_session,
+                registration::sessions::update_session,
+                registration::sessions::delete_session,
+                registration::sessions::import_sessions,
+                registration::users::list_admin_users,
+                registration::users::create_admin_user,
+                registration::users::delete_admin_user,
+                registration::logins::list_admin_logins,
+                registration::logins::revoke_admin_login,
+                registration::two_factor::setup_totp,
+                registration::two_factor::enable_totp,
+                registration::two_factor::disable_totp,
+                registration::two_factor::reset_admin_totp,
+                registration::windows::list_windows,
+                registration::windows::create_window,
+                registration::windows::update_window,
+                registration::windows::delete_window,
+                routes::live::live_updates,
             ],
         )
         .mount("/", FileServer::from(&config.static_dir))
-        .register("/", catchers![routes::not_found, routes::unauthorized])
+        .register("/", catchers![routes::not_found])
+        .register("/api", catchers![routes::api_error])
 }
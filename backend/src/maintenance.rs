@@ -0,0 +1,105 @@
+// Periodic background cleanup of stale rows
+
+use rocket::{Orbit, Rocket};
+use rocket_db_pools::Database;
+use rocket_db_pools::diesel::prelude::*;
+use std::time::Duration;
+
+use crate::AppState;
+use crate::auth::idle_cutoff;
+use crate::booking::{self, Promotion};
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::events::broadcast_availability;
+use crate::mail::Mailer;
+use crate::notify::notify_promoted;
+use crate::schema::admin_sessions;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Spawn the cleanup loop once the server is up
+pub async fn spawn_cleanup(rocket: &Rocket<Orbit>) {
+    let pool = match RegistrationDB::fetch(rocket) {
+        Some(db) => (**db).clone(),
+        None => {
+            eprintln!("❌ Database pool not available, cleanup task not started");
+            return;
+        }
+    };
+
+    let config = match rocket.state::<AppConfig>() {
+        Some(config) => config.clone(),
+        None => {
+            eprintln!("❌ Configuration not available, cleanup task not started");
+            return;
+        }
+    };
+
+    let mailer = match rocket.state::<Mailer>() {
+        Some(mailer) => mailer.clone(),
+        None => {
+            eprintln!("❌ Mailer not available, cleanup task not started");
+            return;
+        }
+    };
+
+    // Freed seats are published like from any route
+    let (tx, session_cache) = match rocket.state::<AppState>() {
+        Some(state) => (state.tx.clone(), state.session_cache.clone()),
+        None => {
+            eprintln!("❌ Application state not available, cleanup task not started");
+            return;
+        }
+    };
+
+    rocket::tokio::spawn(async move {
+        let mut interval = rocket::tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let mut conn = match pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    eprintln!("Cleanup could not get a connection: {}", e);
+                    continue;
+                }
+            };
+
+            // Pending registrations whose email confirmation link ran out,
+            // their seats go to the next ones on the waitlist
+            let now = chrono::Local::now().naive_local();
+            let promotion = Promotion::from_config(&config, now);
+            match booking::expire_pending(&mut conn, now, promotion).await {
+                Ok(expired) => {
+                    if expired.removed > 0 {
+                        println!(
+                            "🧹 Removed {} unconfirmed registration(s) with expired links",
+                            expired.removed
+                        );
+                    }
+                    for session_id in expired.session_ids {
+                        broadcast_availability(&mut conn, &tx, &session_cache, session_id).await;
+                    }
+                    notify_promoted(&mut conn, &config, &mailer, &expired.promoted).await;
+                }
+                Err(e) => eprintln!("Error removing expired registrations: {:?}", e),
+            }
+
+            // Admin logins past their absolute or idle timeout
+            match diesel::delete(
+                admin_sessions::table.filter(
+                    admin_sessions::expires_at
+                        .lt(now)
+                        .or(admin_sessions::last_seen_at.lt(idle_cutoff(&config, now))),
+                ),
+            )
+            .execute(&mut conn)
+            .await
+            {
+                Ok(0) => {}
+                Ok(n) => println!("🧹 Removed {} expired admin session(s)", n),
+                Err(e) => eprintln!("Error removing expired admin sessions: {}", e),
+            }
+        }
+    });
+}
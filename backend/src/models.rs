@@ -1,8 +1,14 @@
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rocket::serde::json::serde_json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::diesel::prelude::*;
+use std::collections::BTreeMap;
 
-use crate::schema::{admin_sessions, registrations, sessions, settings};
+use crate::schema::{
+    admin_recovery_codes, admin_sessions, admin_users, audit_log, registration_windows,
+    registrations, sessions, settings,
+};
+use crate::windows::ClosedReason;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
 #[diesel(table_name = sessions)]
@@ -17,6 +23,19 @@ pub struct Session {
     pub turnus: i32,
 }
 
+#[derive(Debug, Clone, Serialize, Insertable, AsChangeset)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = sessions)]
+pub struct NewSession {
+    pub field_code: String,
+    pub field_name: String,
+    pub session_date: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub max_capacity: i32,
+    pub turnus: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
 #[diesel(table_name = registrations)]
 pub struct Registration {
@@ -30,6 +49,9 @@ pub struct Registration {
     pub guardian_email: String,
     pub confirmed: bool,
     pub created_at: Option<NaiveDateTime>,
+    pub waitlisted: bool,
+    /// When the student was checked in at the event
+    pub checked_in_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone, Insertable)]
@@ -42,6 +64,11 @@ pub struct NewRegistration {
     pub guardian_last_name: String,
     pub guardian_phone: String,
     pub guardian_email: String,
+    pub confirmation_token: Option<String>,
+    pub confirmation_expires_at: Option<NaiveDateTime>,
+    pub manage_token: Option<String>,
+    pub waitlisted: bool,
+    pub checkin_code: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -62,6 +89,183 @@ pub struct CreateRegistrationRequest {
     pub guardian_last_name: String,
     pub guardian_phone: String,
     pub guardian_email: String,
+    /// Join the waitlist instead of failing when the session is full
+    #[serde(default)]
+    pub join_waitlist: bool,
+    /// Signed token from `/api/form-token`, issued when the form was loaded
+    #[serde(default)]
+    pub form_token: String,
+    /// Honeypot, hidden from people and left empty by them
+    #[serde(default)]
+    pub website: String,
+}
+
+/// Several students registered by one guardian, all or none are booked
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateBatchRegistrationRequest {
+    pub guardian_first_name: String,
+    pub guardian_last_name: String,
+    pub guardian_phone: String,
+    pub guardian_email: String,
+    pub students: Vec<BatchStudent>,
+    /// Join the waitlist instead of failing when a session is full
+    #[serde(default)]
+    pub join_waitlist: bool,
+    /// Signed token from `/api/form-token`, issued when the form was loaded
+    #[serde(default)]
+    pub form_token: String,
+    /// Honeypot, hidden from people and left empty by them
+    #[serde(default)]
+    pub website: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchStudent {
+    pub session_id: i32,
+    pub student_first_name: String,
+    pub student_last_name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FormToken {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionRequest {
+    pub field_code: String,
+    pub field_name: String,
+    pub session_date: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub max_capacity: i32,
+    pub turnus: i32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RegistrationCreated {
+    pub id: i32,
+    pub manage_token: String,
+    pub waitlist_position: Option<i64>,
+}
+
+/// Created registrations in the order of the requested students
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchRegistrationCreated {
+    pub registrations: Vec<RegistrationCreated>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MoveRegistrationRequest {
+    pub session_id: i32,
+}
+
+/// Code read from a guardian's QR image at the entrance
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CheckinRequest {
+    pub code: String,
+}
+
+/// Period in which registrations are accepted, for all sessions, one turnus or one session
+#[derive(Debug, Clone, Serialize, Queryable, Selectable)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = registration_windows)]
+pub struct RegistrationWindow {
+    pub id: i32,
+    pub turnus: Option<i32>,
+    pub session_id: Option<i32>,
+    pub opens_at: Option<NaiveDateTime>,
+    pub closes_at: Option<NaiveDateTime>,
+    /// Bookings close this many hours before the session starts
+    pub closes_hours_before: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RegistrationWindowRequest {
+    #[serde(default)]
+    pub turnus: Option<i32>,
+    #[serde(default)]
+    pub session_id: Option<i32>,
+    #[serde(default)]
+    pub opens_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub closes_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub closes_hours_before: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Insertable, AsChangeset)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = registration_windows, treat_none_as_null = true)]
+pub struct NewRegistrationWindow {
+    pub turnus: Option<i32>,
+    pub session_id: Option<i32>,
+    pub opens_at: Option<NaiveDateTime>,
+    pub closes_at: Option<NaiveDateTime>,
+    pub closes_hours_before: Option<i32>,
+}
+
+/// What an imported row does to the sessions table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ImportAction {
+    Create,
+    Update,
+    Unchanged,
+    Invalid,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionImportRow {
+    /// Row number in the uploaded sheet
+    pub row: usize,
+    pub action: ImportAction,
+    /// Existing session the row matches by field code, date and start time
+    pub session_id: Option<i32>,
+    /// Session as it will be written, missing for invalid rows
+    pub session: Option<NewSession>,
+    /// Fields that differ from the existing session
+    pub changes: Vec<&'static str>,
+    /// Error code per invalid field
+    pub errors: BTreeMap<String, &'static str>,
+}
+
+/// Diff of an uploaded session sheet against the sessions table
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionImportReport {
+    pub dry_run: bool,
+    pub rows: Vec<SessionImportRow>,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub invalid: usize,
+    /// Existing sessions missing from the sheet, the import keeps them as they are
+    pub untouched: Vec<Session>,
+}
+
+/// Answer of `/api/status`
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RegistrationStatus {
+    /// Manual switch of the admins, registration stays closed while it is off
+    pub enabled: bool,
+    /// Registration is accepted right now according to the switch and the general windows
+    pub open: bool,
+    /// When a closed registration opens next
+    pub opens_at: Option<NaiveDateTime>,
+    /// When an open registration closes
+    pub closes_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize)]
@@ -76,6 +280,12 @@ pub struct SessionWithAvailability {
     pub max_capacity: i32,
     pub turnus: i32,
     pub available_spots: i32,
+    /// A full session still accepts registrations onto its waitlist
+    pub waitlist_open: bool,
+    /// The session takes bookings right now
+    pub registration_open: bool,
+    /// Why `registration_open` is false, unless registration is switched off as a whole
+    pub closed_reason: Option<ClosedReason>,
 }
 
 #[derive(Debug, Serialize)]
@@ -90,17 +300,53 @@ pub struct RegistrationResponse {
     pub guardian_phone: String,
     pub guardian_email: String,
     pub confirmed: bool,
+    pub waitlisted: bool,
+    pub waitlist_position: Option<i64>,
+    /// Another registration has the same student and guardian contact
+    pub suspected_duplicate: bool,
     pub created_at: String,
+    pub checked_in_at: Option<String>,
+}
+
+impl From<(Registration, Session)> for RegistrationResponse {
+    fn from((reg, session): (Registration, Session)) -> Self {
+        RegistrationResponse {
+            id: reg.id,
+            session,
+            student_first_name: reg.student_first_name,
+            student_last_name: reg.student_last_name,
+            guardian_first_name: reg.guardian_first_name,
+            guardian_last_name: reg.guardian_last_name,
+            guardian_phone: reg.guardian_phone,
+            guardian_email: reg.guardian_email,
+            confirmed: reg.confirmed,
+            waitlisted: reg.waitlisted,
+            waitlist_position: None,
+            suspected_duplicate: false,
+            created_at: reg
+                .created_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            checked_in_at: reg
+                .checked_in_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+        }
+    }
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
 #[diesel(table_name = admin_sessions)]
 pub struct AdminSession {
+    pub id: i32,
+    #[serde(skip_serializing)]
     pub session_token: String,
     pub created_at: Option<NaiveDateTime>,
     pub expires_at: Option<NaiveDateTime>,
+    pub last_seen_at: Option<NaiveDateTime>,
     pub ip_address: Option<String>,
+    pub user_id: i32,
+    /// Password was accepted but the TOTP code is still missing
+    pub pending_totp: bool,
 }
 
 #[derive(Debug, Clone, Insertable)]
@@ -108,11 +354,219 @@ pub struct AdminSession {
 pub struct NewAdminSession {
     pub session_token: String,
     pub expires_at: Option<NaiveDateTime>,
+    pub last_seen_at: Option<NaiveDateTime>,
     pub ip_address: Option<String>,
+    pub user_id: i32,
+    pub pending_totp: bool,
+}
+
+/// An active admin login as listed for revocation
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminSessionInfo {
+    #[serde(flatten)]
+    pub session: AdminSession,
+    pub username: String,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+/// Permission level of an admin account. Managers can do everything viewers can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum AdminRole {
+    /// Can read and export registrations
+    Viewer,
+    /// Can additionally confirm, delete, toggle registration and manage sessions and users
+    Manager,
+}
+
+impl AdminRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "viewer",
+            AdminRole::Manager => "manager",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(AdminRole::Viewer),
+            "manager" => Some(AdminRole::Manager),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = admin_users)]
+pub struct AdminUser {
+    pub id: i32,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: String,
+    pub created_at: Option<NaiveDateTime>,
+    /// Base32 TOTP secret, set once enrollment has started
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    /// Last accepted TOTP time step, codes from it or earlier are replays
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = admin_users)]
+pub struct NewAdminUser {
+    pub username: String,
+    pub password_hash: String,
+    pub role: String,
+}
+
+/// The admin behind an authenticated request
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminIdentity {
+    pub user_id: i32,
+    pub username: String,
+    pub role: AdminRole,
+    pub totp_enabled: bool,
+}
+
+impl AdminIdentity {
+    pub fn from_user(user: &AdminUser) -> Option<Self> {
+        let Some(role) = AdminRole::parse(&user.role) else {
+            eprintln!("Admin user {} has unknown role {}", user.username, user.role);
+            return None;
+        };
+
+        Some(AdminIdentity {
+            user_id: user.id,
+            username: user.username.clone(),
+            role,
+            totp_enabled: user.totp_enabled,
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct AdminLoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Result of the password step of an admin login
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminLoginResponse {
+    /// The login has to be completed with a TOTP or recovery code
+    pub totp_required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TotpCodeRequest {
+    /// Six digit TOTP code or a recovery code
+    pub code: String,
+}
+
+/// Provisioning data for an authenticator app
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TotpSetup {
+    pub secret: String,
+    pub otpauth_url: String,
+    pub qr_svg: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = admin_recovery_codes)]
+pub struct NewRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateAdminUserRequest {
+    pub username: String,
     pub password: String,
+    pub role: AdminRole,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLogRow {
+    pub id: i32,
+    pub created_at: Option<NaiveDateTime>,
+    pub actor_id: Option<i32>,
+    pub actor: Option<String>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub before_data: Option<String>,
+    pub after_data: Option<String>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLogRow {
+    pub actor_id: Option<i32>,
+    pub actor: Option<String>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub before_data: Option<String>,
+    pub after_data: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditLogEntry {
+    pub id: i32,
+    pub created_at: String,
+    pub actor_id: Option<i32>,
+    pub actor: Option<String>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_id: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl From<AuditLogRow> for AuditLogEntry {
+    fn from(row: AuditLogRow) -> Self {
+        let parse = |data: Option<String>| data.and_then(|d| serde_json::from_str(&d).ok());
+        AuditLogEntry {
+            id: row.id,
+            created_at: row
+                .created_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            actor_id: row.actor_id,
+            actor: row.actor,
+            ip_address: row.ip_address,
+            action: row.action,
+            target_id: row.target_id,
+            before: parse(row.before_data),
+            after: parse(row.after_data),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLogEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
 }
@@ -0,0 +1,82 @@
+// Emails sent to guardians after their booking changed without their action
+
+use chrono::NaiveDateTime;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+
+use crate::config::AppConfig;
+use crate::mail::Mailer;
+use crate::models::{Registration, Session};
+use crate::schema::{registrations, sessions};
+
+/// Tell guardians that their waitlisted registration got a seat. With email
+/// confirmation the message carries the link to confirm it and is always sent,
+/// otherwise only when `waitlist_notify` is enabled. Failures are only logged.
+pub async fn notify_promoted(
+    db: &mut AsyncMysqlConnection,
+    config: &AppConfig,
+    mailer: &Mailer,
+    registration_ids: &[i32],
+) {
+    if !(config.waitlist_notify || config.email_confirmation) || registration_ids.is_empty() {
+        return;
+    }
+
+    let promoted = match registrations::table
+        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
+        .filter(registrations::id.eq_any(registration_ids))
+        .select((
+            Registration::as_select(),
+            Session::as_select(),
+            registrations::confirmation_token,
+            registrations::confirmation_expires_at,
+        ))
+        .load::<(Registration, Session, Option<String>, Option<NaiveDateTime>)>(db)
+        .await
+    {
+        Ok(promoted) => promoted,
+        Err(e) => {
+            eprintln!("Error loading promoted registrations: {}", e);
+            return;
+        }
+    };
+
+    let base_url = config.public_base_url.trim_end_matches('/');
+    for (reg, session, token, expires_at) in promoted {
+        let mut body = format!(
+            "Dobrý deň,\n\n\
+             na odborovom dni {} ({}, {} - {}) sa uvoľnilo miesto.\n",
+            session.field_name,
+            session.session_date.format("%d.%m.%Y"),
+            session.start_time.format("%H:%M"),
+            session.end_time.format("%H:%M"),
+        );
+        match (token, expires_at) {
+            (Some(token), Some(expires_at)) if !reg.confirmed => body.push_str(&format!(
+                "Miesto je rezervované pre žiaka {} {}. Prihlášku prosím potvrďte kliknutím na odkaz:\n\
+                 {}/confirmed?token={}\n\n\
+                 Odkaz je platný do {}, potom miesto ponúkneme ďalšiemu v poradí.\n",
+                reg.student_first_name,
+                reg.student_last_name,
+                base_url,
+                token,
+                expires_at.format("%d.%m.%Y %H:%M"),
+            )),
+            _ => body.push_str(&format!(
+                "Žiak {} {} bol presunutý z poradovníka medzi prihlásených.\n",
+                reg.student_first_name, reg.student_last_name,
+            )),
+        }
+
+        if let Err(e) = mailer
+            .send(
+                &reg.guardian_email,
+                "Uvoľnilo sa miesto na odborovom dni",
+                body,
+            )
+            .await
+        {
+            eprintln!("Error sending waitlist promotion email: {}", e);
+        }
+    }
+}
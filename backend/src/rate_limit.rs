@@ -0,0 +1,295 @@
+// In-memory throttling of endpoints that are cheap to hammer
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+// Lockout after the first failure past the free attempts, doubled on every further failure
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+// Window of the global failed login counter
+const GLOBAL_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Counters stay usable even if a holder panicked
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Counts hits per key within a sliding time window
+pub struct WindowCounter<K> {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<K, VecDeque<Instant>>>,
+}
+
+impl<K: Eq + Hash> WindowCounter<K> {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn prune(&self, queue: &mut VecDeque<Instant>, now: Instant) {
+        while queue
+            .front()
+            .is_some_and(|hit| now.duration_since(*hit) >= self.window)
+        {
+            queue.pop_front();
+        }
+    }
+
+    /// Time until `key` may be hit again, `None` if it is below the limit
+    pub fn check(&self, key: &K, now: Instant) -> Option<Duration> {
+        self.check_many(key, 1, now)
+    }
+
+    /// Time until `key` may be hit `count` more times, `None` if that stays within the limit
+    pub fn check_many(&self, key: &K, count: usize, now: Instant) -> Option<Duration> {
+        let mut hits = lock(&self.hits);
+        let Some(queue) = hits.get_mut(key) else {
+            return (count > self.limit).then_some(self.window);
+        };
+        self.prune(queue, now);
+
+        if queue.len() + count <= self.limit {
+            return None;
+        }
+        // The oldest hits have to expire until the new ones fit
+        let wait = queue
+            .get(queue.len() + count - self.limit - 1)
+            .map_or(self.window, |hit| {
+                (*hit + self.window).saturating_duration_since(now)
+            });
+        Some(wait)
+    }
+
+    pub fn hit(&self, key: K, now: Instant) {
+        let mut hits = lock(&self.hits);
+        hits.retain(|_, queue| {
+            self.prune(queue, now);
+            !queue.is_empty()
+        });
+        hits.entry(key).or_default().push_back(now);
+    }
+}
+
+struct FailureStreak {
+    failures: u32,
+    last: Instant,
+}
+
+/// Failed admin login tracking with per-IP exponential backoff. Past the global
+/// ceiling only clients with failures on record have to wait for the window to
+/// pass, so an attack from many addresses can't lock out everybody else.
+pub struct LoginThrottle {
+    free_attempts: u32,
+    streaks: Mutex<HashMap<IpAddr, FailureStreak>>,
+    global: WindowCounter<()>,
+}
+
+impl LoginThrottle {
+    pub fn new(free_attempts: u32, global_limit: usize) -> Self {
+        Self {
+            free_attempts,
+            streaks: Mutex::new(HashMap::new()),
+            global: WindowCounter::new(global_limit, GLOBAL_WINDOW),
+        }
+    }
+
+    fn lockout(&self, failures: u32) -> Duration {
+        if failures < self.free_attempts {
+            return Duration::ZERO;
+        }
+        let doublings = (failures - self.free_attempts).min(16);
+        (BASE_LOCKOUT * 2u32.pow(doublings)).min(MAX_LOCKOUT)
+    }
+
+    /// Reserve an attempt for the client, or return how long it has to wait.
+    /// The attempt counts as failed until it is released or the login succeeds,
+    /// so a burst of parallel requests can't all pass before the first one fails.
+    pub fn check(&self, ip: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let Some(ip) = ip else {
+            // Clients without an address can't be told apart
+            return self.global.check(&(), now).map_or(Ok(()), Err);
+        };
+
+        let mut streaks = lock(&self.streaks);
+        streaks.retain(|_, streak| now.duration_since(streak.last) < MAX_LOCKOUT);
+        let streak = streaks.entry(ip).or_insert(FailureStreak {
+            failures: 0,
+            last: now,
+        });
+
+        if streak.failures > 0
+            && let Some(wait) = self.global.check(&(), now)
+        {
+            return Err(wait);
+        }
+
+        let until = streak.last + self.lockout(streak.failures);
+        if until > now {
+            return Err(until - now);
+        }
+
+        streak.failures += 1;
+        streak.last = now;
+        Ok(())
+    }
+
+    /// Reserve an attempt like `check`, released again when the returned guard is
+    /// dropped before it is marked as failed or successful
+    pub fn attempt(&self, ip: Option<IpAddr>, now: Instant) -> Result<LoginAttempt<'_>, Duration> {
+        self.check(ip, now)?;
+        Ok(LoginAttempt {
+            throttle: self,
+            ip,
+            settled: false,
+        })
+    }
+
+    /// Record that a reserved attempt failed, returning the lockout it puts on the client
+    pub fn failure(&self, ip: Option<IpAddr>, now: Instant) -> Duration {
+        self.global.hit((), now);
+
+        let Some(ip) = ip else {
+            return Duration::ZERO;
+        };
+
+        let mut streaks = lock(&self.streaks);
+        match streaks.get_mut(&ip) {
+            Some(streak) => {
+                streak.last = now;
+                self.lockout(streak.failures)
+            }
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Give back a reserved attempt that did not fail, such as a correct
+    /// password still waiting for its second factor
+    pub fn release(&self, ip: Option<IpAddr>) {
+        let Some(ip) = ip else {
+            return;
+        };
+        if let Some(streak) = lock(&self.streaks).get_mut(&ip) {
+            streak.failures = streak.failures.saturating_sub(1);
+        }
+    }
+
+    /// Forget the failures of a client that logged in successfully
+    pub fn success(&self, ip: Option<IpAddr>) {
+        if let Some(ip) = ip {
+            lock(&self.streaks).remove(&ip);
+        }
+    }
+}
+
+/// Attempt reserved by `LoginThrottle::attempt`. Errors that say nothing about
+/// the credentials, such as a failed database query, just drop it to give it back.
+pub struct LoginAttempt<'a> {
+    throttle: &'a LoginThrottle,
+    ip: Option<IpAddr>,
+    settled: bool,
+}
+
+impl LoginAttempt<'_> {
+    /// The credentials were wrong, returns the lockout put on the client
+    pub fn fail(mut self, now: Instant) -> Duration {
+        self.settled = true;
+        self.throttle.failure(self.ip, now)
+    }
+
+    /// The client logged in
+    pub fn succeed(mut self) {
+        self.settled = true;
+        self.throttle.success(self.ip);
+    }
+}
+
+impl Drop for LoginAttempt<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.throttle.release(self.ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+    const OTHER: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2)));
+
+    #[test]
+    fn window_counter_fits_several_hits_at_once() {
+        let counter = WindowCounter::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+        counter.hit(1, now);
+
+        assert_eq!(counter.check_many(&1, 2, now), None);
+        assert_eq!(
+            counter.check_many(&1, 3, now + Duration::from_secs(10)),
+            Some(Duration::from_secs(50))
+        );
+        assert_eq!(counter.check_many(&2, 3, now), None);
+        assert!(counter.check_many(&2, 4, now).is_some());
+    }
+
+    #[test]
+    fn parallel_attempts_are_reserved_before_they_fail() {
+        let throttle = LoginThrottle::new(3, 100);
+        let now = Instant::now();
+
+        // A burst checked before any attempt has failed gets only the free attempts
+        let allowed = (0..10)
+            .filter(|_| throttle.check(CLIENT, now).is_ok())
+            .count();
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn released_attempts_do_not_count() {
+        let throttle = LoginThrottle::new(1, 100);
+        let now = Instant::now();
+
+        assert!(throttle.check(CLIENT, now).is_ok());
+        throttle.release(CLIENT);
+        assert!(throttle.check(CLIENT, now).is_ok());
+        assert_eq!(throttle.failure(CLIENT, now), BASE_LOCKOUT);
+        assert!(throttle.check(CLIENT, now).is_err());
+
+        throttle.success(CLIENT);
+        assert!(throttle.check(CLIENT, now).is_ok());
+    }
+
+    #[test]
+    fn dropped_attempts_are_released() {
+        let throttle = LoginThrottle::new(1, 100);
+        let now = Instant::now();
+
+        drop(throttle.attempt(CLIENT, now).unwrap());
+        let attempt = throttle.attempt(CLIENT, now).unwrap();
+        assert_eq!(attempt.fail(now), BASE_LOCKOUT);
+        assert!(throttle.attempt(CLIENT, now).is_err());
+    }
+
+    #[test]
+    fn global_ceiling_spares_clients_without_failures() {
+        let throttle = LoginThrottle::new(5, 2);
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            throttle.check(CLIENT, now).unwrap();
+            throttle.failure(CLIENT, now);
+        }
+
+        assert!(throttle.check(CLIENT, now).is_err());
+        assert!(throttle.check(OTHER, now).is_ok());
+        assert!(throttle.check(None, now).is_err());
+    }
+}
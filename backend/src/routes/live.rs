@@ -0,0 +1,57 @@
+use rocket::State;
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::serde::json::serde_json;
+use rocket_ws as ws;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::AppState;
+use crate::events::LiveEvent;
+
+// WebSocket endpoint streaming registration status and seat availability changes
+#[get("/ws")]
+pub fn live_updates(ws: ws::WebSocket, state: &State<AppState>) -> ws::Channel<'static> {
+    let mut rx = state.tx.subscribe();
+    let enabled = AtomicBool::load(&state.registration_enabled, Ordering::Relaxed);
+
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            // Let the client know the current status right away
+            let initial = LiveEvent::RegistrationStatus { enabled };
+            if let Ok(payload) = serde_json::to_string(&initial) {
+                stream.send(ws::Message::Text(payload)).await?;
+            }
+
+            loop {
+                rocket::tokio::select! {
+                    event = rx.recv() => match event {
+                        Ok(event) => {
+                            let payload = match serde_json::to_string(&event) {
+                                Ok(payload) => payload,
+                                Err(e) => {
+                                    eprintln!("Error serializing live event: {}", e);
+                                    continue;
+                                }
+                            };
+                            stream.send(ws::Message::Text(payload)).await?;
+                        }
+                        // A slow client missed seat changes of some sessions, so it
+                        // has to reload them all instead of waiting for later events
+                        Err(RecvError::Lagged(_)) => {
+                            if let Ok(payload) = serde_json::to_string(&LiveEvent::Resync) {
+                                stream.send(ws::Message::Text(payload)).await?;
+                            }
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    message = stream.next() => match message {
+                        Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
+                    },
+                }
+            }
+
+            Ok(())
+        })
+    })
+}
@@ -1,17 +1,22 @@
 // Routes module - organizes all HTTP route handlers
 
+pub mod live;
 pub mod registration;
 
+use rocket::Request;
 use rocket::fs::NamedFile;
 use rocket::http::Status;
 
+use crate::error::ApiError;
+
 /// 404 error handler - serves custom 404.html page
 #[catch(404)]
 pub async fn not_found() -> Option<NamedFile> {
     NamedFile::open("/app/static/404.html").await.ok()
 }
 
-#[catch(401)]
-pub fn unauthorized() -> Status {
-    Status::Unauthorized
+/// Error handler for everything under /api - answers with a JSON error body
+#[catch(default)]
+pub fn api_error(status: Status, _req: &Request) -> ApiError {
+    ApiError::from(status)
 }
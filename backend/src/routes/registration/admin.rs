@@ -4,80 +4,289 @@ use rocket::http::{ContentType, Cookie, CookieJar, SameSite, Status};
 use rocket::serde::json::Json;
 use rocket_db_pools::Connection;
 use rocket_db_pools::diesel::prelude::*;
-use rust_xlsxwriter::Workbook;
+use std::net::IpAddr;
 use std::sync::atomic::Ordering;
+use std::time::Instant;
 use uuid::Uuid;
 
+use super::two_factor::{check_second_factor, unix_time};
 use crate::AppState;
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::Manager;
+use crate::booking;
+use crate::config::AppConfig;
 use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::events::{LiveEvent, publish_availability};
+use crate::export::{self, ExportFormat, SheetGrouping};
+use crate::mail::Mailer;
 use crate::models::{
-    AdminLoginRequest, NewAdminSession, Registration, RegistrationResponse, Session,
+    AdminIdentity, AdminLoginRequest, AdminLoginResponse, AdminUser, AuditLogEntry, AuditLogPage,
+    AuditLogRow, NewAdminSession, Registration, RegistrationResponse, Session, TotpCodeRequest,
 };
-use crate::schema::{admin_sessions, registrations, sessions, settings};
+use crate::notify::notify_promoted;
+use crate::schema::{admin_sessions, admin_users, audit_log, registrations, sessions, settings};
+use crate::spam::SpamStatsSnapshot;
+use crate::validation::invalid_field;
 
-// Helper function to check if admin is authenticated
-async fn is_admin_authenticated(
-    cookies: &CookieJar<'_>,
+// Helper function to load a registration row for audit snapshots
+async fn load_registration(
     db: &mut Connection<RegistrationDB>,
-) -> bool {
-    if let Some(cookie) = cookies.get("admin_auth") {
-        let token = cookie.value();
-        admin_sessions::table
-            .find(token)
-            .count()
-            .get_result::<i64>(db)
-            .await
-            .unwrap_or(0)
-            > 0
-    } else {
-        false
-    }
+    id: i32,
+) -> Result<Registration, ApiError> {
+    registrations::table
+        .find(id)
+        .select(Registration::as_select())
+        .first::<Registration>(db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading registration"))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Registrácia neexistuje"))
+}
+
+// Minutes a password-only login may wait for its TOTP code
+const PENDING_TOTP_MINUTES: i64 = 5;
+
+// bcrypt hash (default cost) of a throwaway password, verified for unknown usernames
+const DUMMY_PASSWORD_HASH: &str = "$2b$12$8F2d3Sjj3/ld5P5Doqi6ZuB.lFZKvWsdM2ifsQWyXXQDGrFaSFV9i";
+
+// Helper function to hand out the admin session cookie
+fn set_auth_cookie(cookies: &CookieJar<'_>, token: String, minutes: i64) {
+    let mut cookie = Cookie::new("admin_auth", token);
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_path("/");
+    cookie.set_max_age(rocket::time::Duration::minutes(minutes));
+    cookies.add(cookie);
 }
 
 // Admin login endpoint
 #[post("/admin/login", format = "json", data = "<login>")]
 pub async fn admin_login(
     mut db: Connection<RegistrationDB>,
-    state: &State<AppState>,
     cookies: &CookieJar<'_>,
+    config: &State<AppConfig>,
+    state: &State<AppState>,
+    ip: Option<IpAddr>,
     login: Json<AdminLoginRequest>,
-) -> Result<Status, Status> {
-    if verify(&login.password, &state.admin_password_hash).unwrap_or(false) {
-        let token = Uuid::new_v4().to_string();
-        let new_session = NewAdminSession {
-            session_token: token.clone(),
-            expires_at: None,
-            ip_address: None,
-        };
-
-        diesel::insert_into(admin_sessions::table)
-            .values(&new_session)
-            .execute(&mut db)
+) -> Result<Json<AdminLoginResponse>, ApiError> {
+    let now = Instant::now();
+    // Given back on any error that is not a wrong password
+    let attempt = match state.login_throttle.attempt(ip, now) {
+        Ok(attempt) => attempt,
+        Err(wait) => {
+            eprintln!("Throttled admin login from {:?}", ip);
+            return Err(ApiError::too_many_requests(wait));
+        }
+    };
+
+    let user = admin_users::table
+        .filter(admin_users::username.eq(&login.username))
+        .first::<AdminUser>(&mut db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading admin user"))?;
+
+    // bcrypt is deliberately slow, keep it off the async workers. Unknown usernames
+    // are checked against a dummy hash so they take as long to reject.
+    let password = login.password.clone();
+    let hash = match &user {
+        Some(user) => user.password_hash.clone(),
+        None => DUMMY_PASSWORD_HASH.to_string(),
+    };
+    let verified =
+        rocket::tokio::task::spawn_blocking(move || verify(password, &hash).unwrap_or(false))
             .await
-            .map_err(|e| {
-                eprintln!("Error creating admin session: {}", e);
-                Status::InternalServerError
-            })?;
-
-        let mut cookie = Cookie::new("admin_auth", token);
-        cookie.set_http_only(true);
-        cookie.set_same_site(SameSite::Lax);
-        cookie.set_path("/");
-        cookies.add(cookie);
-        Ok(Status::Ok)
-    } else {
+            .unwrap_or(false)
+            && user.is_some();
+
+    let Some(user) = user.filter(|_| verified) else {
+        let lockout = attempt.fail(now);
+        audit::record(
+            &mut db,
+            AuditEntry {
+                action: "admin_login_failed",
+                ip,
+                after: snapshot(&rocket::serde::json::json!({
+                    "username": login.username,
+                    "lockout_seconds": lockout.as_secs(),
+                })),
+                ..Default::default()
+            },
+        )
+        .await;
+
         // Clear any existing invalid cookie
         cookies.remove(Cookie::from("admin_auth"));
-        Err(Status::Unauthorized)
+        return Err(ApiError::new(
+            Status::Unauthorized,
+            "invalid_credentials",
+            "Nesprávne meno alebo heslo",
+        ));
+    };
+
+    // With TOTP enabled the password only opens a short-lived pending session
+    let totp_required = user.totp_enabled;
+    let lifetime = if totp_required {
+        PENDING_TOTP_MINUTES
+    } else {
+        config.admin_session_ttl_minutes
+    };
+
+    let token = Uuid::new_v4().to_string();
+    let now = chrono::Local::now().naive_local();
+    let new_session = NewAdminSession {
+        session_token: token.clone(),
+        expires_at: Some(now + chrono::Duration::minutes(lifetime)),
+        last_seen_at: Some(now),
+        ip_address: ip.map(|ip| ip.to_string()),
+        user_id: user.id,
+        pending_totp: totp_required,
+    };
+
+    diesel::insert_into(admin_sessions::table)
+        .values(&new_session)
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error creating admin session"))?;
+
+    // The password was right, with TOTP the dropped attempt is released and
+    // the second factor decides whether the login fails
+    if !totp_required {
+        attempt.succeed();
+        audit::record(
+            &mut db,
+            AuditEntry {
+                action: "admin_login",
+                actor: AdminIdentity::from_user(&user).as_ref(),
+                ip,
+                target_id: Some(user.id.to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
     }
+
+    set_auth_cookie(cookies, token, lifetime);
+    Ok(Json(AdminLoginResponse { totp_required }))
+}
+
+// Second login step for admins with TOTP enabled
+#[post("/admin/login/totp", format = "json", data = "<request>")]
+pub async fn admin_login_totp(
+    mut db: Connection<RegistrationDB>,
+    cookies: &CookieJar<'_>,
+    config: &State<AppConfig>,
+    state: &State<AppState>,
+    ip: Option<IpAddr>,
+    request: Json<TotpCodeRequest>,
+) -> Result<Status, ApiError> {
+    let now = Instant::now();
+    // Given back on any error that is not a wrong code
+    let attempt = match state.login_throttle.attempt(ip, now) {
+        Ok(attempt) => attempt,
+        Err(wait) => {
+            eprintln!("Throttled admin TOTP login from {:?}", ip);
+            return Err(ApiError::too_many_requests(wait));
+        }
+    };
+
+    let token = cookies
+        .get("admin_auth")
+        .map(|c| c.value().to_string())
+        .ok_or(Status::Unauthorized)?;
+    let timestamp = chrono::Local::now().naive_local();
+
+    let user = admin_sessions::table
+        .inner_join(admin_users::table)
+        .filter(admin_sessions::session_token.eq(&token))
+        .filter(admin_sessions::pending_totp.eq(true))
+        .filter(admin_sessions::expires_at.gt(timestamp))
+        .select(AdminUser::as_select())
+        .first::<AdminUser>(&mut db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading pending admin session"))?
+        .ok_or(Status::Unauthorized)?;
+
+    let identity = AdminIdentity::from_user(&user).ok_or(Status::Unauthorized)?;
+    let method = check_second_factor(&mut db, &user, &request.code, unix_time()).await?;
+
+    let Some(method) = method else {
+        let lockout = attempt.fail(now);
+        audit::record(
+            &mut db,
+            AuditEntry {
+                action: "admin_totp_failed",
+                actor: Some(&identity),
+                ip,
+                target_id: Some(user.id.to_string()),
+                after: snapshot(&rocket::serde::json::json!({
+                    "lockout_seconds": lockout.as_secs(),
+                })),
+                ..Default::default()
+            },
+        )
+        .await;
+        return Err(ApiError::new(
+            Status::Unauthorized,
+            "invalid_code",
+            "Nesprávny overovací kód",
+        ));
+    };
+
+    diesel::update(admin_sessions::table.find(&token))
+        .set((
+            admin_sessions::pending_totp.eq(false),
+            admin_sessions::expires_at.eq(
+                timestamp + chrono::Duration::minutes(config.admin_session_ttl_minutes),
+            ),
+            admin_sessions::last_seen_at.eq(timestamp),
+        ))
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error completing admin session"))?;
+
+    attempt.succeed();
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "admin_login",
+            actor: Some(&identity),
+            ip,
+            target_id: Some(user.id.to_string()),
+            after: snapshot(&rocket::serde::json::json!({ "second_factor": method })),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    set_auth_cookie(cookies, token, config.admin_session_ttl_minutes);
+    Ok(Status::Ok)
 }
 
 // Admin logout endpoint
 #[post("/admin/logout")]
 pub async fn admin_logout(
     mut db: Connection<RegistrationDB>,
+    admin: Option<AdminIdentity>,
     cookies: &CookieJar<'_>,
-) -> Result<Status, Status> {
+    ip: Option<IpAddr>,
+) -> Result<Status, ApiError> {
+    if let Some(admin) = admin {
+        audit::record(
+            &mut db,
+            AuditEntry {
+                action: "admin_logout",
+                actor: Some(&admin),
+                ip,
+                target_id: Some(admin.user_id.to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+    }
+
     if let Some(cookie) = cookies.get("admin_auth") {
         let token = cookie.value();
         diesel::delete(admin_sessions::table.find(token))
@@ -91,210 +300,125 @@ pub async fn admin_logout(
 
 // Check if admin is authenticated
 #[get("/admin/check")]
-pub async fn admin_check(
-    mut db: Connection<RegistrationDB>,
-    cookies: &CookieJar<'_>,
-) -> Result<Json<bool>, Status> {
-    let authenticated = is_admin_authenticated(cookies, &mut db).await;
-    Ok(Json(authenticated))
+pub async fn admin_check(admin: Option<AdminIdentity>) -> Json<bool> {
+    Json(admin.is_some())
+}
+
+// Return the logged in admin and their role
+#[get("/admin/me")]
+pub async fn admin_me(admin: AdminIdentity) -> Json<AdminIdentity> {
+    Json(admin)
+}
+
+// Route to get counts of registration attempts rejected as spam - requires authentication.
+// Counts are kept in memory and start from zero at `since`, the last server start.
+#[get("/admin/spam-stats")]
+pub async fn get_spam_stats(
+    _admin: AdminIdentity,
+    state: &State<AppState>,
+) -> Json<SpamStatsSnapshot> {
+    Json(state.spam_stats.snapshot())
 }
 
 // Route to get all registrations (admin view) - requires authentication
 #[get("/admin/registrations")]
 pub async fn get_all_registrations(
     mut db: Connection<RegistrationDB>,
-    cookies: &CookieJar<'_>,
-) -> Result<Json<Vec<RegistrationResponse>>, Status> {
-    // Check authentication
-    if !is_admin_authenticated(cookies, &mut db).await {
-        return Err(Status::Unauthorized);
-    }
+    _admin: AdminIdentity,
+) -> Result<Json<Vec<RegistrationResponse>>, ApiError> {
     let all_registrations = registrations::table
         .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
         .select((Registration::as_select(), Session::as_select()))
         .load::<(Registration, Session)>(&mut db)
         .await
-        .map_err(|e| {
-            eprintln!("Error loading registrations: {}", e);
-            Status::InternalServerError
-        })?;
+        .map_err(ApiError::internal("Error loading registrations"))?;
 
+    let positions = booking::waitlist_positions(&all_registrations);
+    let duplicates = booking::suspected_duplicates(&all_registrations);
     let response: Vec<RegistrationResponse> = all_registrations
         .into_iter()
-        .map(|(reg, session)| RegistrationResponse {
-            id: reg.id,
-            session,
-            student_first_name: reg.student_first_name,
-            student_last_name: reg.student_last_name,
-            guardian_first_name: reg.guardian_first_name,
-            guardian_last_name: reg.guardian_last_name,
-            guardian_phone: reg.guardian_phone,
-            guardian_email: reg.guardian_email,
-            confirmed: reg.confirmed,
-            created_at: reg
-                .created_at
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_default(),
+        .map(|row| {
+            let mut response = RegistrationResponse::from(row);
+            response.waitlist_position = positions.get(&response.id).copied();
+            response.suspected_duplicate = duplicates.contains(&response.id);
+            response
         })
         .collect();
 
     Ok(Json(response))
 }
 
-// Route to export registrations to Excel - requires authentication
-#[get("/admin/registrations/export?<include_unconfirmed>")]
-pub async fn export_registrations_excel(
+// Route to export registrations as Excel, CSV or JSON Lines - requires authentication.
+// `group` splits the Excel workbook into sheets, the other formats ignore it.
+#[get("/admin/registrations/export?<include_unconfirmed>&<format>&<group>")]
+pub async fn export_registrations(
     mut db: Connection<RegistrationDB>,
-    cookies: &CookieJar<'_>,
+    admin: AdminIdentity,
+    state: &State<AppState>,
+    ip: Option<IpAddr>,
     include_unconfirmed: Option<bool>,
-) -> Result<(ContentType, Vec<u8>), Status> {
-    // Check authentication
-    if !is_admin_authenticated(cookies, &mut db).await {
-        return Err(Status::Unauthorized);
-    }
-
+    format: Option<ExportFormat>,
+    group: Option<SheetGrouping>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
     let include_unconfirmed = include_unconfirmed.unwrap_or(false);
+    let format = format.unwrap_or_default();
+    let group = group.unwrap_or_default();
 
-    let mut query = registrations::table
-        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
-        .select((Registration::as_select(), Session::as_select()))
-        .into_boxed();
-
-    if !include_unconfirmed {
-        query = query.filter(registrations::confirmed.eq(true));
-    }
-
-    let all_registrations = query
-        .load::<(Registration, Session)>(&mut db)
+    let all_registrations = export::load_rows(&mut db, include_unconfirmed)
         .await
-        .map_err(|e| {
-            eprintln!("Error loading registrations: {}", e);
-            Status::InternalServerError
-        })?;
-
-    // Create Excel
-    let mut workbook = Workbook::new();
-    let worksheet = workbook.add_worksheet();
-
-    // Headers
-    let headers = [
-        "ID",
-        "Meno študenta",
-        "Priezvisko študenta",
-        "Meno zákonného zástupcu",
-        "Priezvisko zákonného zástupcu",
-        "Email",
-        "Telefón",
-        "Turnus",
-        "Dátum",
-        "Začiatok",
-        "Koniec",
-        "Odbor",
-        "Názov odboru",
-        "Potvrdené",
-        "Vytvorené",
-    ];
-
-    for (col, header) in headers.iter().enumerate() {
-        worksheet
-            .write_string(0, col as u16, *header)
-            .map_err(|_| Status::InternalServerError)?;
-    }
-
-    for (i, (reg, session)) in all_registrations.iter().enumerate() {
-        let row = (i + 1) as u32;
-        worksheet
-            .write_number(row, 0, reg.id as f64)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 1, &reg.student_first_name)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 2, &reg.student_last_name)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 3, &reg.guardian_first_name)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 4, &reg.guardian_last_name)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 5, &reg.guardian_email)
-            .map_err(|_| Status::InternalServerError)?;
-        worksheet
-            .write_string(row, 6, &reg.guardian_phone)
-            .map_err(|_| Status::InternalServerError)?;
-
-        worksheet
-            .write_number(row, 7, session.turnus as f64)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let date_str = session.session_date.format("%d.%m.%Y").to_string();
-        worksheet
-            .write_string(row, 8, &date_str)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let start_str = session.start_time.format("%H:%M").to_string();
-        worksheet
-            .write_string(row, 9, &start_str)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let end_str = session.end_time.format("%H:%M").to_string();
-        worksheet
-            .write_string(row, 10, &end_str)
-            .map_err(|_| Status::InternalServerError)?;
-
-        worksheet
-            .write_string(row, 11, &session.field_code)
-            .map_err(|_| Status::InternalServerError)?;
-
-        worksheet
-            .write_string(row, 12, &session.field_name)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let confirmed = if reg.confirmed { "Áno" } else { "Nie" };
-        worksheet
-            .write_string(row, 13, confirmed)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let created_at = reg
-            .created_at
-            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-            .unwrap_or_default();
-        worksheet
-            .write_string(row, 14, &created_at)
-            .map_err(|_| Status::InternalServerError)?;
-    }
-
-    worksheet.autofit();
-    // Add autofilter to all columns
-    worksheet.autofilter(0, 0, all_registrations.len() as u32, (headers.len() - 1) as u16).map_err(|_| Status::InternalServerError)?;
-
-    let buf = workbook.save_to_buffer().map_err(|e| {
-        eprintln!("Error saving excel buffer: {}", e);
-        Status::InternalServerError
-    })?;
-
-    Ok((
-        ContentType::new(
-            "application",
-            "vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-        ),
-        buf,
-    ))
+        .map_err(ApiError::internal("Error loading registrations"))?;
+    let records = export::records(&all_registrations);
+
+    let buf = match format {
+        ExportFormat::Xlsx => {
+            let summary = export::load_summary(&mut db, state.clock.now())
+                .await
+                .map_err(ApiError::internal("Error loading session summary"))?;
+            export::xlsx(&records, group, &summary)
+                .map_err(ApiError::internal("Error writing excel export"))?
+        }
+        ExportFormat::Csv => {
+            export::csv(&records).map_err(ApiError::internal("Error writing csv export"))?
+        }
+        ExportFormat::Jsonl => {
+            export::jsonl(&records).map_err(ApiError::internal("Error writing jsonl export"))?
+        }
+    };
+
+    // Exports contain personal data, record who took them. Excel exports keep
+    // the action name they were logged under before other formats existed.
+    let action = match format {
+        ExportFormat::Xlsx => "export_registrations_excel",
+        ExportFormat::Csv | ExportFormat::Jsonl => "export_registrations",
+    };
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action,
+            actor: Some(&admin),
+            ip,
+            after: snapshot(&rocket::serde::json::json!({
+                "format": format.as_str(),
+                "group": group.as_str(),
+                "include_unconfirmed": include_unconfirmed,
+                "rows": records.len(),
+            })),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok((format.content_type(), buf))
 }
 
 // Route to toggle registration status - requires authentication
 #[post("/admin/toggle")]
 pub async fn toggle_registration(
     mut db: Connection<RegistrationDB>,
+    admin: Manager,
     state: &State<AppState>,
-    cookies: &CookieJar<'_>,
-) -> Result<Json<bool>, Status> {
-    // Check authentication
-    if !is_admin_authenticated(cookies, &mut db).await {
-        return Err(Status::Unauthorized);
-    }
+    ip: Option<IpAddr>,
+) -> Result<Json<bool>, ApiError> {
     let current =
         std::sync::atomic::AtomicBool::load(&state.registration_enabled, Ordering::Relaxed);
     let new_value = !current;
@@ -303,15 +427,27 @@ pub async fn toggle_registration(
         .set(settings::value.eq(if new_value { "true" } else { "false" }))
         .execute(&mut db)
         .await
-        .map_err(|e| {
-            eprintln!("Error updating registration status: {}", e);
-            Status::InternalServerError
-        })?;
+        .map_err(ApiError::internal("Error updating registration status"))?;
 
     state
         .registration_enabled
         .store(new_value, Ordering::Relaxed);
 
+    let _ = state.tx.send(LiveEvent::RegistrationStatus { enabled: new_value });
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "toggle_registration",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some("registration_enabled".to_string()),
+            before: snapshot(&current),
+            after: snapshot(&new_value),
+        },
+    )
+    .await;
+
     Ok(Json(new_value))
 }
 
@@ -319,22 +455,29 @@ pub async fn toggle_registration(
 #[post("/admin/registrations/<id>/confirm")]
 pub async fn confirm_registration(
     mut db: Connection<RegistrationDB>,
-    cookies: &CookieJar<'_>,
+    admin: Manager,
+    state: &State<AppState>,
+    ip: Option<IpAddr>,
     id: i32,
-) -> Result<Status, Status> {
-    // Check authentication
-    if !is_admin_authenticated(cookies, &mut db).await {
-        return Err(Status::Unauthorized);
-    }
-
-    diesel::update(registrations::table.find(id))
-        .set(registrations::confirmed.eq(true))
-        .execute(&mut db)
-        .await
-        .map_err(|e| {
-            eprintln!("Error confirming registration: {}", e);
-            Status::InternalServerError
-        })?;
+) -> Result<Status, ApiError> {
+    let before = load_registration(&mut db, id).await?;
+    let session_id = booking::confirm_registration(&mut db, id).await?;
+    let after = load_registration(&mut db, id).await?;
+
+    publish_availability(&mut db, state, session_id).await;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "confirm_registration",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            after: snapshot(&after),
+        },
+    )
+    .await;
 
     Ok(Status::Ok)
 }
@@ -343,21 +486,88 @@ pub async fn confirm_registration(
 #[delete("/admin/registrations/<id>")]
 pub async fn delete_registration(
     mut db: Connection<RegistrationDB>,
-    cookies: &CookieJar<'_>,
+    admin: Manager,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    ip: Option<IpAddr>,
     id: i32,
-) -> Result<Status, Status> {
-    // Check authentication
-    if !is_admin_authenticated(cookies, &mut db).await {
-        return Err(Status::Unauthorized);
-    }
-
-    diesel::delete(registrations::table.find(id))
-        .execute(&mut db)
-        .await
-        .map_err(|e| {
-            eprintln!("Error deleting registration: {}", e);
-            Status::InternalServerError
-        })?;
+) -> Result<Status, ApiError> {
+    let before = load_registration(&mut db, id).await?;
+    let promotion = booking::Promotion::from_config(config, state.clock.now());
+    let (session_id, promoted) = booking::delete_registration(&mut db, id, promotion).await?;
+
+    publish_availability(&mut db, state, session_id).await;
+    notify_promoted(&mut db, config, mailer, &promoted).await;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "delete_registration",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            after: snapshot(&rocket::serde::json::json!({ "promoted": promoted })),
+        },
+    )
+    .await;
 
     Ok(Status::Ok)
 }
+
+// Route to page through the audit log - requires manager role
+#[get("/admin/audit?<page>&<per_page>&<action>&<actor>&<target_id>")]
+pub async fn get_audit_log(
+    mut db: Connection<RegistrationDB>,
+    _admin: Manager,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    action: Option<&str>,
+    actor: Option<&str>,
+    target_id: Option<&str>,
+) -> Result<Json<AuditLogPage>, ApiError> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(50).clamp(1, 500);
+    // A huge page number would overflow the offset
+    let offset = (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| invalid_field("page", "out_of_range"))?;
+
+    // The same filters are needed for the count and the page query
+    let filtered = || {
+        let mut query = audit_log::table.into_boxed();
+        if let Some(action) = action {
+            query = query.filter(audit_log::action.eq(action));
+        }
+        if let Some(actor) = actor {
+            query = query.filter(audit_log::actor.eq(actor));
+        }
+        if let Some(target_id) = target_id {
+            query = query.filter(audit_log::target_id.eq(target_id));
+        }
+        query
+    };
+
+    let total: i64 = filtered()
+        .count()
+        .get_result(&mut db)
+        .await
+        .map_err(ApiError::internal("Error counting audit log entries"))?;
+
+    let rows = filtered()
+        .order(audit_log::id.desc())
+        .limit(per_page)
+        .offset(offset)
+        .select(AuditLogRow::as_select())
+        .load::<AuditLogRow>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading audit log"))?;
+
+    Ok(Json(AuditLogPage {
+        entries: rows.into_iter().map(AuditLogEntry::from).collect(),
+        total,
+        page,
+        per_page,
+    }))
+}
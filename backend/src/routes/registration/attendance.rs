@@ -0,0 +1,122 @@
+use rocket::State;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::attendance::{self, SessionSheet, SheetFont};
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{AdminIdentity, Registration, Session};
+use crate::schema::{registrations, sessions};
+use crate::validation::invalid_field;
+
+// Helper function to read the configured sheet font, Helvetica when there is none.
+// A configured font that can't be read fails the request instead of quietly
+// printing names without their diacritics.
+async fn load_font(config: &AppConfig) -> Result<SheetFont, ApiError> {
+    let Some(path) = &config.pdf_font_path else {
+        return Ok(SheetFont::Builtin);
+    };
+    match rocket::tokio::fs::read(path).await {
+        Ok(bytes) => Ok(SheetFont::External(bytes)),
+        Err(e) => {
+            eprintln!("Error reading pdf_font_path {}: {}", path, e);
+            Err(ApiError::new(
+                Status::InternalServerError,
+                "pdf_font_unavailable",
+                "Písmo pre prezenčné listiny sa nepodarilo načítať, skontrolujte pdf_font_path",
+            ))
+        }
+    }
+}
+
+// Route to print attendance sheets of one session or a whole turnus - requires authentication
+#[get("/admin/attendance?<session_id>&<turnus>")]
+pub async fn attendance_sheets(
+    mut db: Connection<RegistrationDB>,
+    admin: AdminIdentity,
+    ip: Option<IpAddr>,
+    config: &State<AppConfig>,
+    session_id: Option<i32>,
+    turnus: Option<i32>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let mut query = sessions::table
+        .order((
+            sessions::session_date.asc(),
+            sessions::start_time.asc(),
+            sessions::field_code.asc(),
+        ))
+        .into_boxed();
+    match (session_id, turnus) {
+        (Some(id), None) => query = query.filter(sessions::id.eq(id)),
+        (None, Some(turnus)) => query = query.filter(sessions::turnus.eq(turnus)),
+        (Some(_), Some(_)) => return Err(invalid_field("session_id", "ambiguous_scope")),
+        (None, None) => return Err(invalid_field("session_id", "required")),
+    }
+
+    let selected = query
+        .load::<Session>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading sessions"))?;
+    if selected.is_empty() {
+        return Err(ApiError::not_found("not_found", "Termín neexistuje"));
+    }
+
+    let session_ids: Vec<i32> = selected.iter().map(|session| session.id).collect();
+    let confirmed = registrations::table
+        .filter(registrations::session_id.eq_any(&session_ids))
+        .filter(registrations::confirmed.eq(true))
+        .order((
+            registrations::student_last_name.asc(),
+            registrations::student_first_name.asc(),
+        ))
+        .select(Registration::as_select())
+        .load::<Registration>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading registrations"))?;
+
+    let mut by_session: HashMap<i32, Vec<Registration>> = HashMap::new();
+    for registration in confirmed {
+        by_session
+            .entry(registration.session_id)
+            .or_default()
+            .push(registration);
+    }
+    let sheets: Vec<SessionSheet> = selected
+        .into_iter()
+        .map(|session| SessionSheet {
+            students: by_session.remove(&session.id).unwrap_or_default(),
+            session,
+        })
+        .collect();
+    let students: usize = sheets.iter().map(|sheet| sheet.students.len()).sum();
+
+    let font = load_font(config).await?;
+    let pdf = attendance::render(&sheets, font)
+        .map_err(ApiError::internal("Error rendering attendance sheets"))?;
+
+    // Sheets list guardian phone numbers, record who printed them
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "export_attendance",
+            actor: Some(&admin),
+            ip,
+            after: snapshot(&json!({
+                "session_id": session_id,
+                "turnus": turnus,
+                "sessions": sheets.len(),
+                "rows": students,
+            })),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok((ContentType::PDF, pdf))
+}
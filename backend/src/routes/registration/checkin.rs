@@ -0,0 +1,45 @@
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use std::net::IpAddr;
+
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::Manager;
+use crate::checkin;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{CheckinRequest, RegistrationResponse};
+use crate::validation::invalid_field;
+
+// Route to check in a student by the code from their QR image - requires manager role.
+// Every code works once, a second scan is rejected with the time of the first.
+#[post("/admin/checkin", format = "json", data = "<checkin_request>")]
+pub async fn check_in(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    checkin_request: Json<CheckinRequest>,
+) -> Result<Json<RegistrationResponse>, ApiError> {
+    let code = checkin_request.code.trim();
+    if code.is_empty() {
+        return Err(invalid_field("code", "required"));
+    }
+
+    // The real time of the scan, not the clock booking rules run on
+    let now = chrono::Local::now().naive_local();
+    let (registration, session) = checkin::check_in(&mut db, code, now).await?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "check_in",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(registration.id.to_string()),
+            after: snapshot(&registration),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(RegistrationResponse::from((registration, session))))
+}
@@ -1,136 +1,380 @@
+use chrono::NaiveDateTime;
 use rocket::State;
 use rocket::http::Status;
+use rocket::response::Redirect;
 use rocket::serde::json::Json;
 use rocket_db_pools::Connection;
 use rocket_db_pools::diesel::prelude::*;
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use uuid::Uuid;
 
 use crate::AppState;
+use crate::booking::{self, Booking, BookingError};
+use crate::config::AppConfig;
 use crate::db::RegistrationDB;
-use crate::models::{CreateRegistrationRequest, NewRegistration, Session, SessionWithAvailability};
-use crate::schema::{registrations, sessions};
+use crate::error::ApiError;
+use crate::events::publish_availability;
+use crate::mail::Mailer;
+use crate::models::{
+    BatchRegistrationCreated, CreateBatchRegistrationRequest, CreateRegistrationRequest,
+    FormToken, NewRegistration, RegistrationCreated, RegistrationStatus, Session,
+    SessionWithAvailability,
+};
+use crate::schema::sessions;
+use crate::spam::SpamRejection;
+use crate::validation::{RegistrationInput, validate_batch_registration, validate_registration};
+use crate::windows;
 
 // Route to get all available sessions with availability info
 #[get("/sessions")]
 pub async fn get_sessions(
     mut db: Connection<RegistrationDB>,
-) -> Result<Json<Vec<SessionWithAvailability>>, Status> {
-    let all_sessions = sessions::table
-        .load::<Session>(&mut db)
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+) -> Result<Json<Vec<SessionWithAvailability>>, ApiError> {
+    let all_sessions = state
+        .session_cache
+        .get(&mut db)
         .await
-        .map_err(|e| {
-            eprintln!("Error loading sessions: {}", e);
-            Status::InternalServerError
-        })?;
-
-    let mut sessions_with_availability = Vec::new();
-
-    for session in all_sessions {
-        // Count registrations for this session
-        let registration_count: i64 = registrations::table
-            .filter(registrations::session_id.eq(session.id))
-            .filter(registrations::confirmed.eq(true))
-            .count()
-            .get_result(&mut db)
-            .await
-            .unwrap_or(0);
-
-        let available_spots = session.max_capacity - registration_count as i32;
-
-        sessions_with_availability.push(SessionWithAvailability {
-            id: session.id,
-            field_code: session.field_code.clone(),
-            field_name: session.field_name.clone(),
-            session_date: session.session_date.format("%Y-%m-%d").to_string(),
-            start_time: session.start_time.format("%H:%M").to_string(),
-            end_time: session.end_time.format("%H:%M").to_string(),
-            max_capacity: session.max_capacity,
-            turnus: session.turnus,
-            available_spots,
-        });
-    }
+        .map_err(ApiError::internal("Error loading sessions"))?;
+
+    let windows = windows::load(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading registration windows"))?;
+    let enabled = AtomicBool::load(&state.registration_enabled, Ordering::Relaxed);
+    let now = state.clock.now();
+
+    let sessions_with_availability = all_sessions
+        .iter()
+        .map(|(session, registration_count)| {
+            let closed_reason =
+                windows::closed_reason(&windows, session, now, config.booking_cutoff_hours);
+
+            SessionWithAvailability {
+                id: session.id,
+                field_code: session.field_code.clone(),
+                field_name: session.field_name.clone(),
+                session_date: session.session_date.format("%Y-%m-%d").to_string(),
+                start_time: session.start_time.format("%H:%M").to_string(),
+                end_time: session.end_time.format("%H:%M").to_string(),
+                max_capacity: session.max_capacity,
+                turnus: session.turnus,
+                available_spots: session.max_capacity - *registration_count as i32,
+                waitlist_open: config.waitlist_enabled,
+                registration_open: enabled && closed_reason.is_none(),
+                closed_reason,
+            }
+        })
+        .collect();
 
     Ok(Json(sessions_with_availability))
 }
 
+// Email the guardian a confirmation link. A failed send is only logged, the
+// admin can still confirm the registration by hand.
+async fn send_confirmation_email(
+    db: &mut Connection<RegistrationDB>,
+    config: &AppConfig,
+    mailer: &Mailer,
+    registration: &NewRegistration,
+) {
+    let (Some(token), Some(expires_at), Some(manage_token)) = (
+        &registration.confirmation_token,
+        registration.confirmation_expires_at,
+        &registration.manage_token,
+    ) else {
+        return;
+    };
+
+    let session = match sessions::table
+        .find(registration.session_id)
+        .first::<Session>(db)
+        .await
+    {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Error loading session for confirmation email: {}", e);
+            return;
+        }
+    };
+
+    let base_url = config.public_base_url.trim_end_matches('/');
+    let link = format!("{}/confirmed?token={}", base_url, token);
+    let manage_link = format!("{}/booking?token={}", base_url, manage_token);
+    let qr_link = format!("{}/api/registrations/{}/checkin.svg", base_url, manage_token);
+    let body = format!(
+        "Dobrý deň,\n\n\
+         prijali sme prihlášku žiaka {} {} na odborový deň {} ({}, {} - {}).\n\n\
+         Prihlášku prosím potvrďte kliknutím na odkaz:\n{}\n\n\
+         Odkaz je platný do {}. Nepotvrdené prihlášky budú automaticky zrušené.\n\n\
+         Prihlášku môžete zobraziť, zrušiť alebo presunúť na iný termín tu:\n{}\n\n\
+         Po potvrdení bude na tomto odkaze QR kód, ktorý žiak ukáže pri príchode:\n{}\n",
+        registration.student_first_name,
+        registration.student_last_name,
+        session.field_name,
+        session.session_date.format("%d.%m.%Y"),
+        session.start_time.format("%H:%M"),
+        session.end_time.format("%H:%M"),
+        link,
+        expires_at.format("%d.%m.%Y %H:%M"),
+        manage_link,
+        qr_link,
+    );
+
+    if let Err(e) = mailer
+        .send(&registration.guardian_email, "Potvrdenie prihlášky na odborový deň", body)
+        .await
+    {
+        eprintln!("Error sending confirmation email: {}", e);
+    }
+}
+
+// Route handing out the form token the registration form has to submit
+#[get("/form-token")]
+pub async fn get_form_token(state: &State<AppState>) -> Json<FormToken> {
+    let now = chrono::Utc::now().timestamp() as u64;
+    Json(FormToken {
+        token: state.form_tokens.issue(now),
+    })
+}
+
+// Helper function rejecting scripted registration attempts. Every student of the
+// request counts as one attempt against the per-IP limit.
+fn check_spam(
+    state: &AppState,
+    ip: Option<IpAddr>,
+    students: usize,
+    website: &str,
+    form_token: &str,
+) -> Result<(), ApiError> {
+    let now = Instant::now();
+    let rejection = if let Some(wait) =
+        ip.and_then(|ip| state.register_limiter.check_many(&ip, students, now))
+    {
+        Err((SpamRejection::RateLimited, ApiError::too_many_requests(wait)))
+    } else if !website.is_empty() {
+        Err((SpamRejection::Honeypot, Status::BadRequest.into()))
+    } else {
+        let unix_now = chrono::Utc::now().timestamp() as u64;
+        state
+            .form_tokens
+            .verify(form_token, unix_now)
+            .map_err(|rejection| {
+                let error = ApiError::bad_request(
+                    "invalid_form_token",
+                    "Platnosť formulára vypršala, obnovte prosím stránku",
+                );
+                (rejection, error)
+            })
+    };
+
+    if let Some(ip) = ip {
+        for _ in 0..students {
+            state.register_limiter.hit(ip, now);
+        }
+    }
+
+    rejection.map_err(|(rejection, response)| {
+        eprintln!("Rejected registration from {:?}: {:?}", ip, rejection);
+        state.spam_stats.record(rejection);
+        response
+    })
+}
+
+// Helper function building a registration row from validated input
+fn new_registration(
+    config: &AppConfig,
+    session_id: i32,
+    input: RegistrationInput,
+    now: NaiveDateTime,
+) -> NewRegistration {
+    // In double opt-in mode the guardian confirms through an emailed one-time link
+    let (confirmation_token, confirmation_expires_at) = if config.email_confirmation {
+        let expires_at = now + chrono::Duration::minutes(config.confirmation_ttl_minutes);
+        (Some(Uuid::new_v4().simple().to_string()), Some(expires_at))
+    } else {
+        (None, None)
+    };
+
+    NewRegistration {
+        session_id,
+        student_first_name: input.student_first_name,
+        student_last_name: input.student_last_name,
+        guardian_first_name: input.guardian_first_name,
+        guardian_last_name: input.guardian_last_name,
+        guardian_phone: input.guardian_phone,
+        guardian_email: input.guardian_email,
+        confirmation_token,
+        confirmation_expires_at,
+        // Secret token for the guardian to view, cancel or reschedule the booking
+        manage_token: Some(Uuid::new_v4().simple().to_string()),
+        waitlisted: false,
+        // Shown as a QR code and scanned at the entrance
+        checkin_code: Some(Uuid::new_v4().simple().to_string()),
+    }
+}
+
+// Helper function for what follows a booking: the confirmation email and the
+// live availability update
+async fn after_booking(
+    db: &mut Connection<RegistrationDB>,
+    state: &AppState,
+    config: &AppConfig,
+    mailer: &Mailer,
+    registration: NewRegistration,
+    booking: Booking,
+) -> RegistrationCreated {
+    // Waitlisted registrations are confirmed by promotion, not by email
+    if booking.waitlist_position.is_none() {
+        send_confirmation_email(db, config, mailer, &registration).await;
+    }
+
+    publish_availability(db, state, registration.session_id).await;
+
+    RegistrationCreated {
+        id: booking.id,
+        manage_token: registration.manage_token.unwrap_or_default(),
+        waitlist_position: booking.waitlist_position,
+    }
+}
+
 // Route to create a new registration
 #[post("/register", format = "json", data = "<registration_request>")]
 pub async fn create_registration(
     mut db: Connection<RegistrationDB>,
     state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    ip: Option<IpAddr>,
     registration_request: Json<CreateRegistrationRequest>,
-) -> Result<Json<i32>, Status> {
-    // Check if registration is enabled
-    if !AtomicBool::load(&state.registration_enabled, Ordering::Relaxed) {
-        return Err(Status::PreconditionFailed); // Registration not enabled
-    }
+) -> Result<Json<RegistrationCreated>, ApiError> {
+    check_spam(
+        state,
+        ip,
+        1,
+        &registration_request.website,
+        &registration_request.form_token,
+    )?;
+    let input = validate_registration(&registration_request)?;
 
-    // Validate session exists and has capacity
-    let session = sessions::table
-        .find(registration_request.session_id)
-        .first::<Session>(&mut db)
-        .await
-        .map_err(|_| Status::NotFound)?;
-
-    // Count current registrations
-    let registration_count: i64 = registrations::table
-        .filter(registrations::session_id.eq(session.id))
-        .filter(registrations::confirmed.eq(true))
-        .count()
-        .get_result(&mut db)
-        .await
-        .map_err(|e| {
-            eprintln!("Error counting registrations: {}", e);
-            Status::InternalServerError
-        })?;
+    // Manual switch and scheduled windows
+    windows::ensure_open(&mut db, state, config, &[registration_request.session_id]).await?;
 
-    if registration_count >= session.max_capacity as i64 {
-        return Err(Status::Conflict); // Session is full
-    }
+    // Create new registration, the capacity check runs in the same transaction
+    let new_registration = new_registration(
+        config,
+        registration_request.session_id,
+        input,
+        state.clock.now(),
+    );
 
-    // Create new registration
-    let new_registration = NewRegistration {
-        session_id: registration_request.session_id,
-        student_first_name: registration_request.student_first_name.clone(),
-        student_last_name: registration_request.student_last_name.clone(),
-        guardian_first_name: registration_request.guardian_first_name.clone(),
-        guardian_last_name: registration_request.guardian_last_name.clone(),
-        guardian_phone: registration_request.guardian_phone.clone(),
-        guardian_email: registration_request.guardian_email.clone(),
-    };
+    let allow_waitlist = config.waitlist_enabled && registration_request.join_waitlist;
+    let booking = booking::create_registration(
+        &mut db,
+        new_registration.clone(),
+        allow_waitlist,
+        config.duplicate_rule,
+    )
+    .await?;
 
-    let result = diesel::insert_into(registrations::table)
-        .values(&new_registration)
-        .execute(&mut db)
-        .await
-        .map_err(|e| {
-            eprintln!("Error creating registration: {}", e);
-            Status::InternalServerError
-        })?;
-
-    if result > 0 {
-        // Get the ID of the inserted registration
-        let registration_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
-            "LAST_INSERT_ID()",
-        ))
-        .get_result::<i32>(&mut db)
-        .await
-        .map_err(|e| {
-            eprintln!("Error getting registration ID: {}", e);
-            Status::InternalServerError
-        })?;
+    let created = after_booking(&mut db, state, config, mailer, new_registration, booking).await;
+    Ok(Json(created))
+}
 
-        Ok(Json(registration_id))
-    } else {
-        Err(Status::InternalServerError)
+// Route for one guardian to register several students at once, e.g. siblings.
+// Capacity of every session is checked in one transaction, all rows are created or none.
+#[post("/register/batch", format = "json", data = "<batch_request>")]
+pub async fn create_batch_registration(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    ip: Option<IpAddr>,
+    batch_request: Json<CreateBatchRegistrationRequest>,
+) -> Result<Json<BatchRegistrationCreated>, ApiError> {
+    check_spam(
+        state,
+        ip,
+        batch_request.students.len(),
+        &batch_request.website,
+        &batch_request.form_token,
+    )?;
+    let inputs = validate_batch_registration(&batch_request)?;
+
+    let session_ids: Vec<i32> = batch_request.students.iter().map(|s| s.session_id).collect();
+    windows::ensure_open(&mut db, state, config, &session_ids).await?;
+
+    let now = state.clock.now();
+    let new_registrations: Vec<NewRegistration> = batch_request
+        .students
+        .iter()
+        .zip(inputs)
+        .map(|(student, input)| new_registration(config, student.session_id, input, now))
+        .collect();
+
+    let allow_waitlist = config.waitlist_enabled && batch_request.join_waitlist;
+    let bookings = booking::create_registrations(
+        &mut db,
+        new_registrations.clone(),
+        allow_waitlist,
+        config.duplicate_rule,
+    )
+    .await?;
+
+    let mut registrations = Vec::with_capacity(bookings.len());
+    for (registration, booking) in new_registrations.into_iter().zip(bookings) {
+        registrations
+            .push(after_booking(&mut db, state, config, mailer, registration, booking).await);
     }
+
+    Ok(Json(BatchRegistrationCreated { registrations }))
 }
 
-// Route to check registration status
+// Route to check registration status, including when it opens or closes next
 #[get("/status")]
-pub async fn get_registration_status(state: &State<AppState>) -> Json<bool> {
-    Json(AtomicBool::load(
-        &state.registration_enabled,
-        Ordering::Relaxed,
-    ))
+pub async fn get_registration_status(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+) -> Result<Json<RegistrationStatus>, ApiError> {
+    let windows = windows::load(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading registration windows"))?;
+    let enabled = AtomicBool::load(&state.registration_enabled, Ordering::Relaxed);
+    let now = state.clock.now();
+
+    Ok(Json(windows::status(enabled, &windows, now)))
+}
+
+// Route for confirmation links sent before they pointed at the frontend page. It only
+// redirects there, mail scanners following the link must not confirm anything.
+#[get("/confirm/<token>")]
+pub fn confirm_email_link(token: &str) -> Redirect {
+    Redirect::to(format!("/confirmed?token={}", token))
+}
+
+// Route posted by the confirmation page, redirects back to it showing the outcome
+#[post("/confirm/<token>")]
+pub async fn confirm_email(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    token: &str,
+) -> Redirect {
+    let now = state.clock.now();
+    let outcome = match booking::confirm_by_token(&mut db, token.to_string(), now).await {
+        Ok(session_id) => {
+            publish_availability(&mut db, state, session_id).await;
+            "ok"
+        }
+        Err(BookingError::NotFound) => "invalid",
+        Err(BookingError::Expired) => "expired",
+        Err(BookingError::Full) => "full",
+        Err(e) => {
+            eprintln!("Error confirming registration by email: {:?}", e);
+            "error"
+        }
+    };
+
+    Redirect::to(format!("/confirmed?status={}", outcome))
 }
@@ -0,0 +1,85 @@
+use rocket::State;
+use rocket::http::{CookieJar, Status};
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+use std::net::IpAddr;
+
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::{Manager, idle_cutoff};
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{AdminSession, AdminSessionInfo};
+use crate::schema::{admin_sessions, admin_users};
+
+// Route to list active admin logins - requires manager role
+#[get("/admin/logins")]
+pub async fn list_admin_logins(
+    mut db: Connection<RegistrationDB>,
+    _admin: Manager,
+    cookies: &CookieJar<'_>,
+    config: &State<AppConfig>,
+) -> Result<Json<Vec<AdminSessionInfo>>, ApiError> {
+    let now = chrono::Local::now().naive_local();
+    let current_token = cookies.get("admin_auth").map(|c| c.value().to_string());
+
+    let logins = admin_sessions::table
+        .inner_join(admin_users::table)
+        .filter(admin_sessions::expires_at.gt(now))
+        .filter(admin_sessions::last_seen_at.gt(idle_cutoff(config, now)))
+        .order(admin_sessions::last_seen_at.desc())
+        .select((AdminSession::as_select(), admin_users::username))
+        .load::<(AdminSession, String)>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading admin logins"))?;
+
+    let response = logins
+        .into_iter()
+        .map(|(session, username)| AdminSessionInfo {
+            current: current_token.as_deref() == Some(session.session_token.as_str()),
+            session,
+            username,
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+// Route to revoke an admin login - requires manager role
+#[delete("/admin/logins/<id>")]
+pub async fn revoke_admin_login(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+) -> Result<Status, ApiError> {
+    let session = admin_sessions::table
+        .filter(admin_sessions::id.eq(id))
+        .select(AdminSession::as_select())
+        .first::<AdminSession>(&mut db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading admin login"))?
+        .ok_or(Status::NotFound)?;
+
+    diesel::delete(admin_sessions::table.filter(admin_sessions::id.eq(id)))
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error revoking admin login"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "revoke_admin_login",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(session.user_id.to_string()),
+            before: snapshot(&session),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
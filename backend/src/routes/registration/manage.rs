@@ -0,0 +1,105 @@
+use rocket::State;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+
+use crate::AppState;
+use crate::booking;
+use crate::checkin;
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::events::publish_availability;
+use crate::mail::Mailer;
+use crate::models::{MoveRegistrationRequest, Registration, RegistrationResponse, Session};
+use crate::notify::notify_promoted;
+use crate::schema::{registrations, sessions};
+use crate::windows;
+
+// Route for a guardian to view their booking using the secret management token
+#[get("/registrations/<token>")]
+pub async fn get_own_registration(
+    mut db: Connection<RegistrationDB>,
+    token: &str,
+) -> Result<Json<RegistrationResponse>, ApiError> {
+    let registration = registrations::table
+        .inner_join(sessions::table.on(registrations::session_id.eq(sessions::id)))
+        .filter(registrations::manage_token.eq(token))
+        .select((Registration::as_select(), Session::as_select()))
+        .first::<(Registration, Session)>(&mut db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading registration"))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Registrácia neexistuje"))?;
+
+    Ok(Json(RegistrationResponse::from(registration)))
+}
+
+// Route serving the check-in QR code of a confirmed booking as SVG
+#[get("/registrations/<token>/checkin.svg")]
+pub async fn own_checkin_qr(
+    mut db: Connection<RegistrationDB>,
+    token: &str,
+) -> Result<(ContentType, String), ApiError> {
+    let code = checkin::code_for_manage_token(&mut db, token).await?;
+    let svg = checkin::qr_svg(&code)
+        .ok_or_else(|| ApiError::internal("Error rendering QR code")("code too long"))?;
+
+    Ok((ContentType::SVG, svg))
+}
+
+// Route for a guardian to cancel their booking
+#[delete("/registrations/<token>")]
+pub async fn cancel_own_registration(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    token: &str,
+) -> Result<Status, ApiError> {
+    let promotion = booking::Promotion::from_config(config, state.clock.now());
+    let (session_id, promoted) =
+        booking::cancel_registration(&mut db, token.to_string(), promotion).await?;
+
+    publish_availability(&mut db, state, session_id).await;
+    notify_promoted(&mut db, config, mailer, &promoted).await;
+
+    Ok(Status::Ok)
+}
+
+// Route for a guardian to move their booking to another session with free spots
+#[post(
+    "/registrations/<token>/move",
+    format = "json",
+    data = "<move_request>"
+)]
+pub async fn move_own_registration(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    token: &str,
+    move_request: Json<MoveRegistrationRequest>,
+) -> Result<Status, ApiError> {
+    // Moving takes a new seat, so it follows the same rules as a new registration
+    windows::ensure_open(&mut db, state, config, &[move_request.session_id]).await?;
+
+    let promotion = booking::Promotion::from_config(config, state.clock.now());
+    let (previous_session_id, session_id, promoted) = booking::move_registration(
+        &mut db,
+        token.to_string(),
+        move_request.session_id,
+        promotion,
+        config.duplicate_rule,
+    )
+    .await?;
+
+    if previous_session_id != session_id {
+        publish_availability(&mut db, state, previous_session_id).await;
+        publish_availability(&mut db, state, session_id).await;
+    }
+    notify_promoted(&mut db, config, mailer, &promoted).await;
+
+    Ok(Status::Ok)
+}
@@ -1,2 +1,10 @@
 pub mod admin;
+pub mod attendance;
+pub mod checkin;
 pub mod client;
+pub mod logins;
+pub mod manage;
+pub mod sessions;
+pub mod two_factor;
+pub mod users;
+pub mod windows;
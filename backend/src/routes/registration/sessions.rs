@@ -0,0 +1,302 @@
+use rocket::State;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::serde::json::{Json, json};
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use std::net::IpAddr;
+
+use crate::AppState;
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::Manager;
+use crate::booking::{BookingError, Promotion, lock_session, promote_waitlist};
+use crate::config::AppConfig;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::events::publish_availability;
+use crate::import::{self, ImportError};
+use crate::mail::Mailer;
+use crate::models::{Session, SessionImportReport, SessionRequest};
+use crate::notify::notify_promoted;
+use crate::schema::{registrations, sessions};
+use crate::validation::validate_session;
+
+// Helper function to load a session or fail with 404
+async fn load_session(db: &mut Connection<RegistrationDB>, id: i32) -> Result<Session, ApiError> {
+    sessions::table
+        .find(id)
+        .first::<Session>(db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading session"))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Termín neexistuje"))
+}
+
+// Route to create a new session - requires authentication
+#[post("/admin/sessions", format = "json", data = "<session_request>")]
+pub async fn create_session(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    session_request: Json<SessionRequest>,
+) -> Result<Json<Session>, ApiError> {
+    let new_session = validate_session(&session_request)?;
+
+    let session = db
+        .transaction(|conn| {
+            async move {
+                diesel::insert_into(sessions::table)
+                    .values(&new_session)
+                    .execute(conn)
+                    .await?;
+
+                let session_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+                    "LAST_INSERT_ID()",
+                ))
+                .get_result::<i32>(conn)
+                .await?;
+
+                sessions::table
+                    .find(session_id)
+                    .first::<Session>(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(ApiError::internal("Error creating session"))?;
+    state.session_cache.invalidate();
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "create_session",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(session.id.to_string()),
+            after: snapshot(&session),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(session))
+}
+
+// Route to update an existing session - requires authentication
+#[put("/admin/sessions/<id>", format = "json", data = "<session_request>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_session(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    ip: Option<IpAddr>,
+    id: i32,
+    session_request: Json<SessionRequest>,
+) -> Result<Json<Session>, ApiError> {
+    let changes = validate_session(&session_request)?;
+
+    let before = load_session(&mut db, id).await?;
+    let promotion = Promotion::from_config(config, state.clock.now());
+
+    let (session, promoted) = db
+        .transaction(|conn| {
+            async move {
+                let (_, occupied) = lock_session(conn, id).await?;
+
+                // Capacity must not drop below the seats already taken
+                if (changes.max_capacity as i64) < occupied {
+                    return Err(BookingError::Full);
+                }
+
+                diesel::update(sessions::table.find(id))
+                    .set(&changes)
+                    .execute(conn)
+                    .await?;
+
+                // Seats added by a higher capacity go to the waitlist first
+                let promoted = promote_waitlist(conn, id, promotion).await?;
+
+                Ok((
+                    sessions::table.find(id).first::<Session>(conn).await?,
+                    promoted,
+                ))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|e| match e {
+            BookingError::Full => ApiError::conflict(
+                "capacity_below_registrations",
+                "Kapacita nemôže byť nižšia ako počet obsadených miest",
+            ),
+            e => e.into(),
+        })?;
+
+    // Capacity may have changed
+    publish_availability(&mut db, state, id).await;
+    notify_promoted(&mut db, config, mailer, &promoted).await;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "update_session",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            after: snapshot(&session),
+        },
+    )
+    .await;
+
+    Ok(Json(session))
+}
+
+// Route to delete a session - requires authentication.
+// Sessions with registrations are only removed when `cascade=true` is given.
+#[delete("/admin/sessions/<id>?<cascade>")]
+pub async fn delete_session(
+    mut db: Connection<RegistrationDB>,
+    state: &State<AppState>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+    cascade: Option<bool>,
+) -> Result<Status, ApiError> {
+    let cascade = cascade.unwrap_or(false);
+
+    let before = load_session(&mut db, id).await?;
+
+    // Counted under the session lock, so no booking can slip in before the delete.
+    // A refusal comes back as `Ok(Err(count))` to leave the transaction untouched.
+    let deleted = db
+        .transaction(|conn| {
+            async move {
+                lock_session(conn, id).await?;
+
+                let registration_count: i64 = registrations::table
+                    .filter(registrations::session_id.eq(id))
+                    .count()
+                    .get_result(conn)
+                    .await?;
+                if registration_count > 0 && !cascade {
+                    return Ok(Err(registration_count));
+                }
+
+                diesel::delete(registrations::table.filter(registrations::session_id.eq(id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(sessions::table.find(id))
+                    .execute(conn)
+                    .await?;
+                Ok::<_, BookingError>(Ok(registration_count))
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let registration_count = deleted.map_err(|registration_count| {
+        ApiError::conflict(
+            "session_has_registrations",
+            "Termín má registrácie, odstránenie treba potvrdiť",
+        )
+        .with_details(json!({ "registrations": registration_count }))
+    })?;
+    state.session_cache.invalidate();
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "delete_session",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            after: snapshot(&rocket::serde::json::json!({
+                "deleted_registrations": registration_count,
+            })),
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
+
+// Route to import sessions from an .xlsx or CSV sheet - requires authentication.
+// Only reports the changes unless `dry_run=false` is given, then writes all
+// rows or none of them.
+#[post("/admin/sessions/import?<dry_run>", data = "<upload>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_sessions(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    state: &State<AppState>,
+    config: &State<AppConfig>,
+    mailer: &State<Mailer>,
+    ip: Option<IpAddr>,
+    dry_run: Option<bool>,
+    upload: Data<'_>,
+) -> Result<Json<SessionImportReport>, ApiError> {
+    let upload = upload
+        .open(5.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(ApiError::internal("Error reading upload"))?;
+    if !upload.is_complete() {
+        return Err(Status::PayloadTooLarge.into());
+    }
+    let rows = import::parse(&upload)?;
+
+    if dry_run.unwrap_or(true) {
+        let report = import::plan(&mut db, rows, true)
+            .await
+            .map_err(ApiError::internal("Error planning import"))?;
+        return Ok(Json(report));
+    }
+
+    let promotion = Promotion::from_config(config, state.clock.now());
+    let (report, updated, promoted) = db
+        .transaction(|conn| {
+            async move {
+                let report = import::plan(conn, rows, false).await?;
+                if report.invalid > 0 {
+                    return Err(ImportError::Invalid(report));
+                }
+                let (updated, promoted) = import::apply(conn, &report, promotion).await?;
+                Ok((report, updated, promoted))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.session_cache.invalidate();
+
+    // Capacities may have changed
+    for id in &updated {
+        publish_availability(&mut db, state, *id).await;
+    }
+    notify_promoted(&mut db, config, mailer, &promoted).await;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "import_sessions",
+            actor: Some(&admin.0),
+            ip,
+            after: snapshot(&json!({
+                "created": report.created,
+                "updated": report.updated,
+                "unchanged": report.unchanged,
+                "untouched": report.untouched.len(),
+            })),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(report))
+}
@@ -0,0 +1,362 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::{self, AuditEntry};
+use crate::auth::Manager;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{
+    AdminIdentity, AdminUser, NewRecoveryCode, RecoveryCodes, TotpCodeRequest, TotpSetup,
+};
+use crate::schema::{admin_recovery_codes, admin_users};
+use crate::totp;
+
+// Helper function returning the current unix time in seconds
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Helper function to load the account behind an admin identity
+async fn load_admin_user(
+    db: &mut Connection<RegistrationDB>,
+    user_id: i32,
+) -> Result<AdminUser, ApiError> {
+    admin_users::table
+        .find(user_id)
+        .first::<AdminUser>(db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading admin user"))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Používateľ neexistuje"))
+}
+
+fn totp_already_enabled() -> ApiError {
+    ApiError::conflict(
+        "totp_already_enabled",
+        "Dvojfaktorové overenie je už zapnuté",
+    )
+}
+
+fn invalid_code() -> ApiError {
+    ApiError::unprocessable("invalid_code", "Nesprávny overovací kód")
+}
+
+// Helper function to check a TOTP or recovery code, consuming it on success.
+// Returns which kind of code was accepted.
+pub(crate) async fn check_second_factor(
+    db: &mut AsyncMysqlConnection,
+    user: &AdminUser,
+    code: &str,
+    time: u64,
+) -> Result<Option<&'static str>, ApiError> {
+    if let Some(step) = user
+        .totp_secret
+        .as_deref()
+        .and_then(|secret| totp::verify(secret, code, time))
+    {
+        let step = step as i64;
+        if user.totp_last_step.is_some_and(|last| step <= last) {
+            return Ok(None);
+        }
+
+        // Conditional update so a code can't be replayed concurrently either
+        let accepted = diesel::update(
+            admin_users::table.find(user.id).filter(
+                admin_users::totp_last_step
+                    .is_null()
+                    .or(admin_users::totp_last_step.lt(step)),
+            ),
+        )
+        .set(admin_users::totp_last_step.eq(step))
+        .execute(db)
+        .await
+        .map_err(ApiError::internal("Error recording TOTP step"))?;
+
+        return Ok((accepted > 0).then_some("totp"));
+    }
+
+    let used = diesel::delete(
+        admin_recovery_codes::table
+            .filter(admin_recovery_codes::user_id.eq(user.id))
+            .filter(admin_recovery_codes::code_hash.eq(totp::hash_recovery_code(code))),
+    )
+    .execute(db)
+    .await
+    .map_err(ApiError::internal("Error checking recovery code"))?;
+
+    Ok((used > 0).then_some("recovery_code"))
+}
+
+// Route to start TOTP enrollment for the logged in admin
+#[post("/admin/totp/setup")]
+pub async fn setup_totp(
+    mut db: Connection<RegistrationDB>,
+    admin: AdminIdentity,
+) -> Result<Json<TotpSetup>, ApiError> {
+    if admin.totp_enabled {
+        return Err(totp_already_enabled());
+    }
+
+    let secret = totp::generate_secret();
+    let setup = totp::provisioning(&secret, &admin.username).ok_or_else(|| {
+        eprintln!(
+            "Error building TOTP provisioning data for {}",
+            admin.username
+        );
+        ApiError::from(Status::InternalServerError)
+    })?;
+
+    diesel::update(admin_users::table.find(admin.user_id))
+        .set((
+            admin_users::totp_secret.eq(&secret),
+            admin_users::totp_last_step.eq(None::<i64>),
+        ))
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error storing TOTP secret"))?;
+
+    Ok(Json(setup))
+}
+
+// Route to finish TOTP enrollment with a code from the authenticator app
+#[post("/admin/totp/enable", format = "json", data = "<request>")]
+pub async fn enable_totp(
+    mut db: Connection<RegistrationDB>,
+    admin: AdminIdentity,
+    ip: Option<IpAddr>,
+    request: Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodes>, ApiError> {
+    let user = load_admin_user(&mut db, admin.user_id).await?;
+    let secret = match (&user.totp_secret, user.totp_enabled) {
+        (Some(secret), false) => secret.clone(),
+        (_, true) => return Err(totp_already_enabled()),
+        (None, false) => {
+            return Err(ApiError::conflict(
+                "totp_setup_required",
+                "Najprv spustite nastavenie dvojfaktorového overenia",
+            ));
+        }
+    };
+
+    let step = totp::verify(&secret, &request.code, unix_time()).ok_or_else(invalid_code)? as i64;
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let new_codes: Vec<NewRecoveryCode> = recovery_codes
+        .iter()
+        .map(|code| NewRecoveryCode {
+            user_id: user.id,
+            code_hash: totp::hash_recovery_code(code),
+        })
+        .collect();
+
+    db.transaction(|conn| {
+        async move {
+            diesel::update(admin_users::table.find(user.id))
+                .set((
+                    admin_users::totp_enabled.eq(true),
+                    admin_users::totp_last_step.eq(step),
+                ))
+                .execute(conn)
+                .await?;
+
+            diesel::delete(
+                admin_recovery_codes::table.filter(admin_recovery_codes::user_id.eq(user.id)),
+            )
+            .execute(conn)
+            .await?;
+
+            diesel::insert_into(admin_recovery_codes::table)
+                .values(&new_codes)
+                .execute(conn)
+                .await?;
+
+            Ok::<_, diesel::result::Error>(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(ApiError::internal("Error enabling TOTP"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "enable_totp",
+            actor: Some(&admin),
+            ip,
+            target_id: Some(admin.user_id.to_string()),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+// Helper function to remove the second factor of an account
+async fn clear_totp(db: &mut Connection<RegistrationDB>, user_id: i32) -> Result<(), ApiError> {
+    db.transaction(|conn| {
+        async move {
+            diesel::update(admin_users::table.find(user_id))
+                .set((
+                    admin_users::totp_enabled.eq(false),
+                    admin_users::totp_secret.eq(None::<String>),
+                    admin_users::totp_last_step.eq(None::<i64>),
+                ))
+                .execute(conn)
+                .await?;
+
+            diesel::delete(
+                admin_recovery_codes::table.filter(admin_recovery_codes::user_id.eq(user_id)),
+            )
+            .execute(conn)
+            .await?;
+
+            Ok::<_, diesel::result::Error>(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(ApiError::internal("Error disabling TOTP"))
+}
+
+// Route to turn off TOTP for the logged in admin, confirmed with a current code
+#[post("/admin/totp/disable", format = "json", data = "<request>")]
+pub async fn disable_totp(
+    mut db: Connection<RegistrationDB>,
+    admin: AdminIdentity,
+    ip: Option<IpAddr>,
+    request: Json<TotpCodeRequest>,
+) -> Result<Status, ApiError> {
+    let user = load_admin_user(&mut db, admin.user_id).await?;
+    if !user.totp_enabled {
+        return Err(ApiError::conflict(
+            "totp_not_enabled",
+            "Dvojfaktorové overenie nie je zapnuté",
+        ));
+    }
+
+    check_second_factor(&mut db, &user, &request.code, unix_time())
+        .await?
+        .ok_or_else(invalid_code)?;
+
+    clear_totp(&mut db, user.id).await?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "disable_totp",
+            actor: Some(&admin),
+            ip,
+            target_id: Some(admin.user_id.to_string()),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
+
+// Route to reset TOTP of another admin who lost their device - requires manager role
+#[delete("/admin/users/<id>/totp")]
+pub async fn reset_admin_totp(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+) -> Result<Status, ApiError> {
+    load_admin_user(&mut db, id).await?;
+    clear_totp(&mut db, id).await?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "reset_admin_totp",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AdminRole;
+    use crate::test_db;
+
+    // RFC 6238 test secret "12345678901234567890" in base32
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const TIME: u64 = 1_111_111_109;
+    const CODE: &str = "081804";
+
+    async fn load_user(conn: &mut AsyncMysqlConnection, user_id: i32) -> AdminUser {
+        admin_users::table
+            .find(user_id)
+            .first::<AdminUser>(conn)
+            .await
+            .expect("Failed to load test admin")
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn totp_codes_are_accepted_once() {
+        let mut conn = test_db::connect().await;
+        let (user_id, _) = test_db::insert_admin(&mut conn, AdminRole::Manager).await;
+        diesel::update(admin_users::table.find(user_id))
+            .set(admin_users::totp_secret.eq(SECRET))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to store test secret");
+
+        let stale = load_user(&mut conn, user_id).await;
+        let first = check_second_factor(&mut conn, &stale, CODE, TIME).await;
+        // Replayed with the recorded step, and with a copy loaded before the first use
+        let fresh = load_user(&mut conn, user_id).await;
+        let replayed = check_second_factor(&mut conn, &fresh, CODE, TIME).await;
+        let raced = check_second_factor(&mut conn, &stale, CODE, TIME).await;
+        test_db::delete_admin(&mut conn, user_id).await;
+
+        assert_eq!(first.ok().flatten(), Some("totp"));
+        assert_eq!(replayed.ok().flatten(), None);
+        assert_eq!(raced.ok().flatten(), None);
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn recovery_codes_are_single_use() {
+        let mut conn = test_db::connect().await;
+        let (user_id, _) = test_db::insert_admin(&mut conn, AdminRole::Manager).await;
+        let code = totp::generate_recovery_codes().remove(0);
+        diesel::insert_into(admin_recovery_codes::table)
+            .values(&NewRecoveryCode {
+                user_id,
+                code_hash: totp::hash_recovery_code(&code),
+            })
+            .execute(&mut conn)
+            .await
+            .expect("Failed to store test recovery code");
+
+        let user = load_user(&mut conn, user_id).await;
+        // Typed in lower case without the dash, as users tend to
+        let typed = code.replace('-', "").to_lowercase();
+        let first = check_second_factor(&mut conn, &user, &typed, TIME).await;
+        let second = check_second_factor(&mut conn, &user, &code, TIME).await;
+        test_db::delete_admin(&mut conn, user_id).await;
+
+        assert_eq!(first.ok().flatten(), Some("recovery_code"));
+        assert_eq!(second.ok().flatten(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use bcrypt::{DEFAULT_COST, hash};
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+use std::net::IpAddr;
+
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::Manager;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{AdminUser, CreateAdminUserRequest, NewAdminUser};
+use crate::schema::admin_users;
+use crate::validation::invalid_field;
+
+// Route to list admin accounts - requires manager role
+#[get("/admin/users")]
+pub async fn list_admin_users(
+    mut db: Connection<RegistrationDB>,
+    _admin: Manager,
+) -> Result<Json<Vec<AdminUser>>, ApiError> {
+    let users = admin_users::table
+        .order(admin_users::username.asc())
+        .load::<AdminUser>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading admin users"))?;
+
+    Ok(Json(users))
+}
+
+// Route to create an admin account - requires manager role
+#[post("/admin/users", format = "json", data = "<user_request>")]
+pub async fn create_admin_user(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    user_request: Json<CreateAdminUserRequest>,
+) -> Result<Status, ApiError> {
+    let username = user_request.username.trim();
+    if username.is_empty() || username.chars().count() > 50 {
+        return Err(invalid_field("username", "invalid"));
+    }
+    if user_request.password.len() < 8 {
+        return Err(invalid_field("password", "too_short"));
+    }
+
+    let existing: i64 = admin_users::table
+        .filter(admin_users::username.eq(username))
+        .count()
+        .get_result(&mut db)
+        .await
+        .map_err(ApiError::internal("Error checking admin username"))?;
+
+    if existing > 0 {
+        return Err(ApiError::conflict(
+            "username_taken",
+            "Používateľ s týmto menom už existuje",
+        ));
+    }
+
+    let password_hash = hash(&user_request.password, DEFAULT_COST)
+        .map_err(ApiError::internal("Error hashing password"))?;
+
+    diesel::insert_into(admin_users::table)
+        .values(&NewAdminUser {
+            username: username.to_string(),
+            password_hash,
+            role: user_request.role.as_str().to_string(),
+        })
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error creating admin user"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "create_admin_user",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(username.to_string()),
+            after: snapshot(&rocket::serde::json::json!({
+                "username": username,
+                "role": user_request.role,
+            })),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Created)
+}
+
+// Route to delete an admin account - requires manager role.
+// Admins cannot delete themselves, so at least one manager always remains.
+#[delete("/admin/users/<id>")]
+pub async fn delete_admin_user(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+) -> Result<Status, ApiError> {
+    if admin.0.user_id == id {
+        return Err(ApiError::conflict(
+            "cannot_delete_self",
+            "Nemôžete odstrániť vlastný účet",
+        ));
+    }
+
+    let before = admin_users::table
+        .find(id)
+        .first::<AdminUser>(&mut db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading admin user"))?
+        .ok_or(Status::NotFound)?;
+
+    // Sessions of the deleted user are removed by the foreign key cascade
+    diesel::delete(admin_users::table.find(id))
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error deleting admin user"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "delete_admin_user",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
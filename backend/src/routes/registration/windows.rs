@@ -0,0 +1,197 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+use rocket_db_pools::diesel::prelude::*;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use std::net::IpAddr;
+
+use crate::audit::{self, AuditEntry, snapshot};
+use crate::auth::Manager;
+use crate::db::RegistrationDB;
+use crate::error::ApiError;
+use crate::models::{
+    AdminIdentity, NewRegistrationWindow, RegistrationWindow, RegistrationWindowRequest,
+};
+use crate::schema::registration_windows;
+use crate::validation::invalid_field;
+
+// Validate a window payload before it is written
+fn validate_window(request: &RegistrationWindowRequest) -> Result<NewRegistrationWindow, ApiError> {
+    if request.turnus.is_some() && request.session_id.is_some() {
+        return Err(invalid_field("session_id", "ambiguous_scope"));
+    }
+    if request.turnus.is_some_and(|turnus| turnus < 1) {
+        return Err(invalid_field("turnus", "too_small"));
+    }
+    if request.opens_at.is_none()
+        && request.closes_at.is_none()
+        && request.closes_hours_before.is_none()
+    {
+        return Err(invalid_field("opens_at", "required"));
+    }
+    if let (Some(opens_at), Some(closes_at)) = (request.opens_at, request.closes_at)
+        && closes_at <= opens_at
+    {
+        return Err(invalid_field("closes_at", "before_open"));
+    }
+    if request.closes_hours_before.is_some_and(|hours| hours < 0) {
+        return Err(invalid_field("closes_hours_before", "too_small"));
+    }
+
+    Ok(NewRegistrationWindow {
+        turnus: request.turnus,
+        session_id: request.session_id,
+        opens_at: request.opens_at,
+        closes_at: request.closes_at,
+        closes_hours_before: request.closes_hours_before,
+    })
+}
+
+// Helper function to load a window or fail with 404
+async fn load_window(
+    db: &mut Connection<RegistrationDB>,
+    id: i32,
+) -> Result<RegistrationWindow, ApiError> {
+    registration_windows::table
+        .find(id)
+        .select(RegistrationWindow::as_select())
+        .first::<RegistrationWindow>(db)
+        .await
+        .optional()
+        .map_err(ApiError::internal("Error loading registration window"))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Registračné okno neexistuje"))
+}
+
+// Route to list registration windows - requires authentication
+#[get("/admin/windows")]
+pub async fn list_windows(
+    mut db: Connection<RegistrationDB>,
+    _admin: AdminIdentity,
+) -> Result<Json<Vec<RegistrationWindow>>, ApiError> {
+    let windows = registration_windows::table
+        .order(registration_windows::id.asc())
+        .select(RegistrationWindow::as_select())
+        .load::<RegistrationWindow>(&mut db)
+        .await
+        .map_err(ApiError::internal("Error loading registration windows"))?;
+
+    Ok(Json(windows))
+}
+
+// Route to create a registration window - requires manager role
+#[post("/admin/windows", format = "json", data = "<window_request>")]
+pub async fn create_window(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    window_request: Json<RegistrationWindowRequest>,
+) -> Result<Json<RegistrationWindow>, ApiError> {
+    let new_window = validate_window(&window_request)?;
+
+    let window = db
+        .transaction(|conn| {
+            async move {
+                diesel::insert_into(registration_windows::table)
+                    .values(&new_window)
+                    .execute(conn)
+                    .await?;
+
+                let window_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+                    "LAST_INSERT_ID()",
+                ))
+                .get_result::<i32>(conn)
+                .await?;
+
+                registration_windows::table
+                    .find(window_id)
+                    .select(RegistrationWindow::as_select())
+                    .first::<RegistrationWindow>(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(ApiError::internal("Error creating registration window"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "create_registration_window",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(window.id.to_string()),
+            after: snapshot(&window),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Json(window))
+}
+
+// Route to update a registration window - requires manager role
+#[put("/admin/windows/<id>", format = "json", data = "<window_request>")]
+pub async fn update_window(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+    window_request: Json<RegistrationWindowRequest>,
+) -> Result<Json<RegistrationWindow>, ApiError> {
+    let changes = validate_window(&window_request)?;
+    let before = load_window(&mut db, id).await?;
+
+    diesel::update(registration_windows::table.find(id))
+        .set(&changes)
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error updating registration window"))?;
+
+    let window = load_window(&mut db, id).await?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "update_registration_window",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            after: snapshot(&window),
+        },
+    )
+    .await;
+
+    Ok(Json(window))
+}
+
+// Route to delete a registration window - requires manager role
+#[delete("/admin/windows/<id>")]
+pub async fn delete_window(
+    mut db: Connection<RegistrationDB>,
+    admin: Manager,
+    ip: Option<IpAddr>,
+    id: i32,
+) -> Result<Status, ApiError> {
+    let before = load_window(&mut db, id).await?;
+
+    diesel::delete(registration_windows::table.find(id))
+        .execute(&mut db)
+        .await
+        .map_err(ApiError::internal("Error deleting registration window"))?;
+
+    audit::record(
+        &mut db,
+        AuditEntry {
+            action: "delete_registration_window",
+            actor: Some(&admin.0),
+            ip,
+            target_id: Some(id.to_string()),
+            before: snapshot(&before),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(Status::Ok)
+}
@@ -8,6 +8,55 @@ diesel::table! {
         expires_at -> Nullable<Timestamp>,
         #[max_length = 45]
         ip_address -> Nullable<Varchar>,
+        user_id -> Integer,
+        id -> Integer,
+        last_seen_at -> Nullable<Timestamp>,
+        pending_totp -> Bool,
+    }
+}
+
+diesel::table! {
+    admin_recovery_codes (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    admin_users (id) {
+        id -> Integer,
+        #[max_length = 50]
+        username -> Varchar,
+        #[max_length = 255]
+        password_hash -> Varchar,
+        #[max_length = 20]
+        role -> Varchar,
+        created_at -> Nullable<Timestamp>,
+        #[max_length = 64]
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<Bigint>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        created_at -> Nullable<Timestamp>,
+        actor_id -> Nullable<Integer>,
+        #[max_length = 50]
+        actor -> Nullable<Varchar>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        #[max_length = 50]
+        action -> Varchar,
+        #[max_length = 64]
+        target_id -> Nullable<Varchar>,
+        before_data -> Nullable<Text>,
+        after_data -> Nullable<Text>,
     }
 }
 
@@ -29,6 +78,15 @@ diesel::table! {
         guardian_email -> Varchar,
         confirmed -> Bool,
         created_at -> Nullable<Timestamp>,
+        #[max_length = 64]
+        confirmation_token -> Nullable<Varchar>,
+        confirmation_expires_at -> Nullable<Timestamp>,
+        #[max_length = 64]
+        manage_token -> Nullable<Varchar>,
+        waitlisted -> Bool,
+        #[max_length = 64]
+        checkin_code -> Nullable<Varchar>,
+        checked_in_at -> Nullable<Timestamp>,
     }
 }
 
@@ -47,6 +105,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    registration_windows (id) {
+        id -> Integer,
+        turnus -> Nullable<Integer>,
+        session_id -> Nullable<Integer>,
+        opens_at -> Nullable<Datetime>,
+        closes_at -> Nullable<Datetime>,
+        closes_hours_before -> Nullable<Integer>,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     settings (key_name) {
         #[max_length = 50]
@@ -56,6 +126,18 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(admin_recovery_codes -> admin_users (user_id));
+diesel::joinable!(admin_sessions -> admin_users (user_id));
+diesel::joinable!(registration_windows -> sessions (session_id));
 diesel::joinable!(registrations -> sessions (session_id));
 
-diesel::allow_tables_to_appear_in_same_query!(admin_sessions, registrations, sessions, settings,);
+diesel::allow_tables_to_appear_in_same_query!(
+    admin_recovery_codes,
+    admin_sessions,
+    admin_users,
+    audit_log,
+    registration_windows,
+    registrations,
+    sessions,
+    settings,
+);
@@ -0,0 +1,235 @@
+// Bot protection for the public registration form
+
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use rocket::serde::Serialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Form tokens older than this are rejected, the page has to be reloaded
+const FORM_TOKEN_MAX_AGE_SECS: u64 = 2 * 60 * 60;
+
+/// Why a registration attempt was rejected as spam
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamRejection {
+    /// Too many attempts from one IP
+    RateLimited,
+    /// The hidden honeypot field was filled in
+    Honeypot,
+    /// Missing, forged, expired or already used form token
+    InvalidToken,
+    /// The form was submitted faster than a human can fill it in
+    TooFast,
+}
+
+/// Signed timestamps proving when the registration form was loaded.
+/// Each token is accepted once, used nonces are kept until the token would expire.
+pub struct FormTokens {
+    key: Vec<u8>,
+    min_fill_secs: u64,
+    used: Mutex<HashMap<String, u64>>,
+}
+
+impl FormTokens {
+    /// Use the configured secret, or a random one that invalidates tokens on restart
+    pub fn new(secret: Option<&str>, min_fill_secs: u64) -> Self {
+        let key = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+        Self {
+            key,
+            min_fill_secs,
+            used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn mac(&self, issued_at: u64, nonce: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(format!("{}.{}", issued_at, nonce).as_bytes());
+        mac
+    }
+
+    /// Token in the form `<issued unix time>.<hex nonce>.<hex signature>`
+    pub fn issue(&self, now: u64) -> String {
+        let mut nonce = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let nonce = encode_hex(&nonce);
+        let signature = encode_hex(&self.mac(now, &nonce).finalize().into_bytes());
+        format!("{}.{}.{}", now, nonce, signature)
+    }
+
+    /// Accept a token once, if it is genuine and neither too old nor too fresh
+    pub fn verify(&self, token: &str, now: u64) -> Result<(), SpamRejection> {
+        let mut parts = token.splitn(3, '.');
+        let (Some(issued_at), Some(nonce), Some(signature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(SpamRejection::InvalidToken);
+        };
+        let issued_at: u64 = issued_at.parse().map_err(|_| SpamRejection::InvalidToken)?;
+        let signature = decode_hex(signature).ok_or(SpamRejection::InvalidToken)?;
+
+        self.mac(issued_at, nonce)
+            .verify_slice(&signature)
+            .map_err(|_| SpamRejection::InvalidToken)?;
+
+        let age = now
+            .checked_sub(issued_at)
+            .ok_or(SpamRejection::InvalidToken)?;
+        if age > FORM_TOKEN_MAX_AGE_SECS {
+            return Err(SpamRejection::InvalidToken);
+        }
+        if age < self.min_fill_secs {
+            return Err(SpamRejection::TooFast);
+        }
+
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        used.retain(|_, issued_at| now.saturating_sub(*issued_at) <= FORM_TOKEN_MAX_AGE_SECS);
+        if used.insert(nonce.to_string(), issued_at).is_some() {
+            return Err(SpamRejection::InvalidToken);
+        }
+        Ok(())
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Rejection counters since the server started, they are kept in memory only
+pub struct SpamStats {
+    since: NaiveDateTime,
+    rate_limited: AtomicU64,
+    honeypot: AtomicU64,
+    invalid_token: AtomicU64,
+    too_fast: AtomicU64,
+}
+
+/// Rejections counted since `since`, the last server start
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SpamStatsSnapshot {
+    pub since: NaiveDateTime,
+    pub rate_limited: u64,
+    pub honeypot: u64,
+    pub invalid_token: u64,
+    pub too_fast: u64,
+}
+
+impl Default for SpamStats {
+    fn default() -> Self {
+        Self {
+            since: chrono::Local::now().naive_local(),
+            rate_limited: AtomicU64::default(),
+            honeypot: AtomicU64::default(),
+            invalid_token: AtomicU64::default(),
+            too_fast: AtomicU64::default(),
+        }
+    }
+}
+
+impl SpamStats {
+    pub fn record(&self, rejection: SpamRejection) {
+        let counter = match rejection {
+            SpamRejection::RateLimited => &self.rate_limited,
+            SpamRejection::Honeypot => &self.honeypot,
+            SpamRejection::InvalidToken => &self.invalid_token,
+            SpamRejection::TooFast => &self.too_fast,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SpamStatsSnapshot {
+        SpamStatsSnapshot {
+            since: self.since,
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            honeypot: self.honeypot.load(Ordering::Relaxed),
+            invalid_token: self.invalid_token.load(Ordering::Relaxed),
+            too_fast: self.too_fast.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUED_AT: u64 = 1_700_000_000;
+
+    #[test]
+    fn form_tokens_are_accepted_once() {
+        let tokens = FormTokens::new(Some("secret"), 3);
+        let token = tokens.issue(ISSUED_AT);
+
+        assert_eq!(tokens.verify(&token, ISSUED_AT + 10), Ok(()));
+        assert_eq!(
+            tokens.verify(&token, ISSUED_AT + 20),
+            Err(SpamRejection::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn tokens_issued_in_the_same_second_differ() {
+        let tokens = FormTokens::new(Some("secret"), 3);
+        let first = tokens.issue(ISSUED_AT);
+        let second = tokens.issue(ISSUED_AT);
+
+        assert_ne!(first, second);
+        assert_eq!(tokens.verify(&first, ISSUED_AT + 10), Ok(()));
+        assert_eq!(tokens.verify(&second, ISSUED_AT + 10), Ok(()));
+    }
+
+    #[test]
+    fn early_submissions_do_not_use_up_the_token() {
+        let tokens = FormTokens::new(Some("secret"), 3);
+        let token = tokens.issue(ISSUED_AT);
+
+        assert_eq!(
+            tokens.verify(&token, ISSUED_AT + 1),
+            Err(SpamRejection::TooFast)
+        );
+        assert_eq!(tokens.verify(&token, ISSUED_AT + 10), Ok(()));
+    }
+
+    #[test]
+    fn forged_and_expired_tokens_are_rejected() {
+        let tokens = FormTokens::new(Some("secret"), 3);
+        let other = FormTokens::new(Some("other"), 3);
+
+        assert_eq!(
+            tokens.verify(&other.issue(ISSUED_AT), ISSUED_AT + 10),
+            Err(SpamRejection::InvalidToken)
+        );
+        assert_eq!(
+            tokens.verify(
+                &tokens.issue(ISSUED_AT),
+                ISSUED_AT + FORM_TOKEN_MAX_AGE_SECS + 1
+            ),
+            Err(SpamRejection::InvalidToken)
+        );
+        assert_eq!(
+            tokens.verify("garbage", ISSUED_AT),
+            Err(SpamRejection::InvalidToken)
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// Helpers for tests that run against a real MySQL database. Those tests are
+// ignored by default, run them with `cargo test -- --ignored` and DATABASE_URL
+// pointing at a scratch database.
+
+use chrono::{NaiveDate, NaiveTime};
+use diesel::Connection;
+use diesel_migrations::MigrationHarness;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::AppState;
+use crate::availability::SessionCache;
+use crate::clock::Clock;
+use crate::db::MIGRATIONS;
+use crate::models::{
+    AdminRole, NewAdminSession, NewAdminUser, NewRegistration, NewSession, Session,
+};
+use crate::rate_limit::{LoginThrottle, WindowCounter};
+use crate::schema::{admin_sessions, admin_users, registrations, sessions};
+use crate::spam::{FormTokens, SpamStats};
+
+pub fn database_url() -> String {
+    dotenvy::dotenv().ok();
+    std::env::var("DATABASE_URL").expect("DATABASE_URL must point at a scratch database")
+}
+
+/// Connection to the test database with every migration applied
+pub async fn connect() -> AsyncMysqlConnection {
+    let url = database_url();
+    let migrate_url = url.clone();
+    rocket::tokio::task::spawn_blocking(move || {
+        let mut conn = diesel::MysqlConnection::establish(&migrate_url)
+            .expect("Failed to connect to the test database");
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Failed to migrate the test database");
+    })
+    .await
+    .expect("Migration task panicked");
+
+    AsyncMysqlConnection::establish(&url)
+        .await
+        .expect("Failed to connect to the test database")
+}
+
+/// Application state with registration enabled and time taken from `clock`
+pub fn app_state(clock: Clock) -> AppState {
+    AppState {
+        registration_enabled: AtomicBool::new(true),
+        tx: broadcast::channel(16).0,
+        login_throttle: LoginThrottle::new(5, 100),
+        register_limiter: WindowCounter::new(100, Duration::from_secs(60 * 60)),
+        form_tokens: FormTokens::new(None, 0),
+        spam_stats: SpamStats::default(),
+        clock,
+        session_cache: Arc::new(SessionCache::new(Duration::ZERO)),
+    }
+}
+
+/// Session far in the future with the given capacity
+pub async fn insert_session(conn: &mut AsyncMysqlConnection, max_capacity: i32) -> Session {
+    diesel::insert_into(sessions::table)
+        .values(&NewSession {
+            field_code: "TST".to_string(),
+            field_name: "Test".to_string(),
+            session_date: NaiveDate::from_ymd_opt(2099, 1, 1).unwrap(),
+            start_time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            max_capacity,
+            turnus: 99,
+        })
+        .execute(conn)
+        .await
+        .expect("Failed to insert test session");
+
+    let id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "LAST_INSERT_ID()",
+    ))
+    .get_result::<i32>(conn)
+    .await
+    .expect("Failed to read test session id");
+
+    sessions::table
+        .find(id)
+        .first::<Session>(conn)
+        .await
+        .expect("Failed to load test session")
+}
+
+/// Pending registration of a distinct student for a session
+pub fn new_registration(session_id: i32, n: usize) -> NewRegistration {
+    NewRegistration {
+        session_id,
+        student_first_name: format!("Student{}", n),
+        student_last_name: "Test".to_string(),
+        guardian_first_name: "Guardian".to_string(),
+        guardian_last_name: "Test".to_string(),
+        guardian_phone: "+421900000000".to_string(),
+        guardian_email: format!("guardian{}@example.com", n),
+        confirmation_token: None,
+        confirmation_expires_at: None,
+        manage_token: None,
+        waitlisted: false,
+        checkin_code: None,
+    }
+}
+
+/// Remove a test session together with its registrations
+pub async fn delete_session(conn: &mut AsyncMysqlConnection, session_id: i32) {
+    diesel::delete(registrations::table.filter(registrations::session_id.eq(session_id)))
+        .execute(conn)
+        .await
+        .expect("Failed to delete test registrations");
+    diesel::delete(sessions::table.find(session_id))
+        .execute(conn)
+        .await
+        .expect("Failed to delete test session");
+}
+
+/// Admin account with the given role and a live login.
+/// Returns the user id and the token for the `admin_auth` cookie.
+pub async fn insert_admin(conn: &mut AsyncMysqlConnection, role: AdminRole) -> (i32, String) {
+    let token = uuid::Uuid::new_v4().to_string();
+    diesel::insert_into(admin_users::table)
+        .values(&NewAdminUser {
+            username: format!("test-{}", &token[..8]),
+            password_hash: "!".to_string(),
+            role: role.as_str().to_string(),
+        })
+        .execute(conn)
+        .await
+        .expect("Failed to insert test admin");
+
+    let user_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "LAST_INSERT_ID()",
+    ))
+    .get_result::<i32>(conn)
+    .await
+    .expect("Failed to read test admin id");
+
+    let now = chrono::Local::now().naive_local();
+    diesel::insert_into(admin_sessions::table)
+        .values(&NewAdminSession {
+            session_token: token.clone(),
+            expires_at: Some(now + chrono::Duration::hours(1)),
+            last_seen_at: Some(now),
+            ip_address: None,
+            user_id,
+            pending_totp: false,
+        })
+        .execute(conn)
+        .await
+        .expect("Failed to insert test admin session");
+
+    (user_id, token)
+}
+
+/// Remove a test admin together with its logins
+pub async fn delete_admin(conn: &mut AsyncMysqlConnection, user_id: i32) {
+    diesel::delete(admin_sessions::table.filter(admin_sessions::user_id.eq(user_id)))
+        .execute(conn)
+        .await
+        .expect("Failed to delete test admin sessions");
+    diesel::delete(admin_users::table.find(user_id))
+        .execute(conn)
+        .await
+        .expect("Failed to delete test admin");
+}
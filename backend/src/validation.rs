@@ -0,0 +1,354 @@
+// Normalization and validation of user submitted registration data
+
+use rocket::serde::json::json;
+use std::collections::BTreeMap;
+
+use crate::error::ApiError;
+use crate::models::{
+    CreateBatchRegistrationRequest, CreateRegistrationRequest, NewSession, SessionRequest,
+};
+
+// Column limits from schema.rs
+const NAME_MAX_LEN: usize = 100;
+const FIELD_CODE_MAX_LEN: usize = 3;
+const EMAIL_MAX_LEN: usize = 255;
+
+// Most students one guardian can register at once
+const BATCH_MAX_STUDENTS: usize = 10;
+
+// Slovak numbers written in national format, e.g. 0905 123 456
+const DEFAULT_COUNTRY_CODE: &str = "421";
+const SLOVAK_NUMBER_DIGITS: usize = 12;
+
+/// Error code per invalid field, e.g. `"guardian_phone": "invalid_phone"`
+#[derive(Debug)]
+pub struct ValidationErrors(BTreeMap<String, &'static str>);
+
+impl ValidationErrors {
+    pub fn into_fields(self) -> BTreeMap<String, &'static str> {
+        self.0
+    }
+}
+
+/// `422 Unprocessable Entity` with the field errors under `details.fields`
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        ApiError::unprocessable("validation_failed", "Niektoré polia sú vyplnené nesprávne")
+            .with_details(json!({ "fields": errors.0 }))
+    }
+}
+
+/// Shorthand for a `422` about a single field
+pub fn invalid_field(field: &str, code: &'static str) -> ApiError {
+    let mut errors = BTreeMap::new();
+    errors.insert(field.to_string(), code);
+    ValidationErrors(errors).into()
+}
+
+/// Collects normalized values and the errors found along the way
+#[derive(Default)]
+pub struct Validator {
+    errors: BTreeMap<String, &'static str>,
+}
+
+impl Validator {
+    fn fail(&mut self, field: &str, code: &'static str) {
+        self.errors.entry(field.to_string()).or_insert(code);
+    }
+
+    /// Trimmed name with inner whitespace collapsed
+    pub fn name(&mut self, field: &str, value: &str) -> String {
+        let name = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            self.fail(field, "required");
+        } else if name.chars().count() > NAME_MAX_LEN {
+            self.fail(field, "too_long");
+        }
+        name
+    }
+
+    /// Trimmed email address with a plausible `local@domain.tld` shape
+    pub fn email(&mut self, field: &str, value: &str) -> String {
+        let email = value.trim().to_string();
+        if email.is_empty() {
+            self.fail(field, "required");
+        } else if email.chars().count() > EMAIL_MAX_LEN {
+            self.fail(field, "too_long");
+        } else if !is_valid_email(&email) {
+            self.fail(field, "invalid_email");
+        }
+        email
+    }
+
+    /// Phone number normalized to E.164, national numbers are taken as Slovak
+    pub fn phone(&mut self, field: &str, value: &str) -> String {
+        if value.trim().is_empty() {
+            self.fail(field, "required");
+            return String::new();
+        }
+        match normalize_phone(value) {
+            Some(phone) => phone,
+            None => {
+                self.fail(field, "invalid_phone");
+                value.trim().to_string()
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.errors))
+        }
+    }
+}
+
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    let labels_ok = domain.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    });
+
+    !local.is_empty()
+        && !local.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && domain.contains('.')
+        && labels_ok
+}
+
+fn normalize_phone(value: &str) -> Option<String> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '/' | '(' | ')' | '.'))
+        .collect();
+
+    let digits = if let Some(rest) = compact.strip_prefix('+') {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix("00") {
+        rest.to_string()
+    } else if let Some(rest) = compact.strip_prefix('0') {
+        format!("{}{}", DEFAULT_COUNTRY_CODE, rest)
+    } else {
+        return None;
+    };
+
+    if !digits.chars().all(|c| c.is_ascii_digit()) || digits.starts_with('0') {
+        return None;
+    }
+    let valid_length = if digits.starts_with(DEFAULT_COUNTRY_CODE) {
+        digits.len() == SLOVAK_NUMBER_DIGITS
+    } else {
+        (8..=15).contains(&digits.len())
+    };
+
+    valid_length.then(|| format!("+{}", digits))
+}
+
+/// Registration fields after normalization
+pub struct RegistrationInput {
+    pub student_first_name: String,
+    pub student_last_name: String,
+    pub guardian_first_name: String,
+    pub guardian_last_name: String,
+    pub guardian_phone: String,
+    pub guardian_email: String,
+}
+
+pub fn validate_registration(
+    request: &CreateRegistrationRequest,
+) -> Result<RegistrationInput, ValidationErrors> {
+    let mut v = Validator::default();
+    let input = RegistrationInput {
+        student_first_name: v.name("student_first_name", &request.student_first_name),
+        student_last_name: v.name("student_last_name", &request.student_last_name),
+        guardian_first_name: v.name("guardian_first_name", &request.guardian_first_name),
+        guardian_last_name: v.name("guardian_last_name", &request.guardian_last_name),
+        guardian_phone: v.phone("guardian_phone", &request.guardian_phone),
+        guardian_email: v.email("guardian_email", &request.guardian_email),
+    };
+    v.finish()?;
+    Ok(input)
+}
+
+/// Validate a batch, student errors are keyed as `students.<index>.<field>`
+pub fn validate_batch_registration(
+    request: &CreateBatchRegistrationRequest,
+) -> Result<Vec<RegistrationInput>, ValidationErrors> {
+    let mut v = Validator::default();
+    let guardian_first_name = v.name("guardian_first_name", &request.guardian_first_name);
+    let guardian_last_name = v.name("guardian_last_name", &request.guardian_last_name);
+    let guardian_phone = v.phone("guardian_phone", &request.guardian_phone);
+    let guardian_email = v.email("guardian_email", &request.guardian_email);
+
+    if request.students.is_empty() {
+        v.fail("students", "required");
+    } else if request.students.len() > BATCH_MAX_STUDENTS {
+        v.fail("students", "too_many");
+    }
+
+    let inputs = request
+        .students
+        .iter()
+        .enumerate()
+        .map(|(i, student)| RegistrationInput {
+            student_first_name: v.name(
+                &format!("students.{}.student_first_name", i),
+                &student.student_first_name,
+            ),
+            student_last_name: v.name(
+                &format!("students.{}.student_last_name", i),
+                &student.student_last_name,
+            ),
+            guardian_first_name: guardian_first_name.clone(),
+            guardian_last_name: guardian_last_name.clone(),
+            guardian_phone: guardian_phone.clone(),
+            guardian_email: guardian_email.clone(),
+        })
+        .collect();
+    v.finish()?;
+    Ok(inputs)
+}
+
+/// Validate and normalize a session before it is written
+pub fn validate_session(request: &SessionRequest) -> Result<NewSession, ValidationErrors> {
+    let mut v = Validator::default();
+    let field_code = request.field_code.trim().to_uppercase();
+    let field_name = request.field_name.trim().to_string();
+
+    if field_code.is_empty() || field_code.chars().count() > FIELD_CODE_MAX_LEN {
+        v.fail("field_code", "invalid");
+    }
+    if field_name.is_empty() || field_name.chars().count() > NAME_MAX_LEN {
+        v.fail("field_name", "invalid");
+    }
+    if request.end_time <= request.start_time {
+        v.fail("end_time", "before_start");
+    }
+    if request.max_capacity < 1 {
+        v.fail("max_capacity", "too_small");
+    }
+    if request.turnus < 1 {
+        v.fail("turnus", "too_small");
+    }
+    v.finish()?;
+
+    Ok(NewSession {
+        field_code,
+        field_name,
+        session_date: request.session_date,
+        start_time: request.start_time,
+        end_time: request.end_time,
+        max_capacity: request.max_capacity,
+        turnus: request.turnus,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::*;
+
+    #[test]
+    fn phone_numbers_are_normalized_to_e164() {
+        assert_eq!(
+            normalize_phone("0900 000 000").as_deref(),
+            Some("+421900000000")
+        );
+        assert_eq!(
+            normalize_phone("00421 900/000-000").as_deref(),
+            Some("+421900000000")
+        );
+        assert_eq!(
+            normalize_phone("+421 (900) 000.000").as_deref(),
+            Some("+421900000000")
+        );
+        assert_eq!(
+            normalize_phone("+420 601 234 567").as_deref(),
+            Some("+420601234567")
+        );
+    }
+
+    #[test]
+    fn malformed_phone_numbers_are_rejected() {
+        assert_eq!(normalize_phone("0900 000 00a"), None);
+        assert_eq!(normalize_phone("0900_000_000"), None);
+        assert_eq!(normalize_phone("900 000 000"), None);
+        assert_eq!(normalize_phone("0900 000 0000"), None);
+        assert_eq!(normalize_phone("+0900000000"), None);
+    }
+
+    #[test]
+    fn emails_need_a_domain_with_a_tld() {
+        assert!(is_valid_email("rodic@example.sk"));
+        assert!(is_valid_email("meno.priezvisko@skola.edu.sk"));
+        assert!(!is_valid_email("rodic@example"));
+        assert!(!is_valid_email("rodic@example."));
+        assert!(!is_valid_email("@example.sk"));
+        assert!(!is_valid_email("rodic@-example.sk"));
+        assert!(!is_valid_email("rodic @example.sk"));
+        assert!(!is_valid_email("rodic@exa mple.sk"));
+    }
+
+    #[test]
+    fn names_are_trimmed_and_collapsed() {
+        let mut v = Validator::default();
+        assert_eq!(v.name("first", "  Ján \t Novák "), "Ján Novák");
+        assert_eq!(v.name("empty", "   "), "");
+        v.name("long", &"a".repeat(NAME_MAX_LEN + 1));
+
+        let fields = v.finish().unwrap_err().into_fields();
+        assert_eq!(fields.get("first"), None);
+        assert_eq!(fields.get("empty"), Some(&"required"));
+        assert_eq!(fields.get("long"), Some(&"too_long"));
+    }
+
+    #[test]
+    fn field_errors_keep_their_codes() {
+        let mut v = Validator::default();
+        assert_eq!(v.email("email", " rodic@example.sk "), "rodic@example.sk");
+        assert_eq!(v.phone("phone", "0900 000 000"), "+421900000000");
+        v.email("bad_email", "rodic@example");
+        v.phone("bad_phone", "12345");
+        v.phone("no_phone", " ");
+
+        let fields = v.finish().unwrap_err().into_fields();
+        assert_eq!(fields.len(), 3, "fields: {:?}", fields);
+        assert_eq!(fields.get("bad_email"), Some(&"invalid_email"));
+        assert_eq!(fields.get("bad_phone"), Some(&"invalid_phone"));
+        assert_eq!(fields.get("no_phone"), Some(&"required"));
+    }
+
+    #[test]
+    fn session_fields_are_normalized() {
+        let request = SessionRequest {
+            field_code: " inf ".to_string(),
+            field_name: " Informatika ".to_string(),
+            session_date: NaiveDate::from_ymd_opt(2026, 3, 1).unwrap(),
+            start_time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+            max_capacity: 20,
+            turnus: 1,
+        };
+        let session = validate_session(&request).unwrap();
+        assert_eq!(session.field_code, "INF");
+        assert_eq!(session.field_name, "Informatika");
+
+        let request = SessionRequest {
+            field_code: "INFO".to_string(),
+            end_time: request.start_time,
+            max_capacity: 0,
+            ..request
+        };
+        let fields = validate_session(&request).unwrap_err().into_fields();
+        assert_eq!(fields.get("field_code"), Some(&"invalid"));
+        assert_eq!(fields.get("end_time"), Some(&"before_start"));
+        assert_eq!(fields.get("max_capacity"), Some(&"too_small"));
+    }
+}
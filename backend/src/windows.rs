@@ -0,0 +1,354 @@
+// When sessions take bookings: the manual registration switch, scheduled
+// registration windows, the booking cutoff and sessions that already started
+
+use chrono::NaiveDateTime;
+use rocket::serde::Serialize;
+use rocket::serde::json::json;
+use rocket_db_pools::diesel::AsyncMysqlConnection;
+use rocket_db_pools::diesel::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::AppState;
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::models::{RegistrationStatus, RegistrationWindow, Session};
+use crate::schema::{registration_windows, sessions};
+
+/// Why a session does not take bookings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ClosedReason {
+    /// The session already started
+    Past,
+    /// The session starts within the booking cutoff
+    Cutoff,
+    /// No registration window governing the session is open
+    Window,
+}
+
+impl From<ClosedReason> for ApiError {
+    fn from(reason: ClosedReason) -> Self {
+        match reason {
+            ClosedReason::Past => ApiError::conflict("session_past", "Termín už prebehol"),
+            ClosedReason::Cutoff => ApiError::conflict(
+                "booking_cutoff",
+                "Prihlasovanie na tento termín sa už skončilo",
+            ),
+            ClosedReason::Window => ApiError::registration_closed(),
+        }
+    }
+}
+
+pub async fn load(
+    conn: &mut AsyncMysqlConnection,
+) -> Result<Vec<RegistrationWindow>, diesel::result::Error> {
+    registration_windows::table
+        .select(RegistrationWindow::as_select())
+        .load(conn)
+        .await
+}
+
+fn session_start(session: &Session) -> NaiveDateTime {
+    session.session_date.and_time(session.start_time)
+}
+
+/// Whether a window accepts bookings at `now` for a session starting at `start`
+fn contains(window: &RegistrationWindow, now: NaiveDateTime, start: Option<NaiveDateTime>) -> bool {
+    let opened = window.opens_at.is_none_or(|at| now >= at);
+    let not_closed = window.closes_at.is_none_or(|at| now < at);
+    let before_deadline = match (window.closes_hours_before, start) {
+        (Some(hours), Some(start)) => now < start - chrono::Duration::hours(hours.into()),
+        _ => true,
+    };
+    opened && not_closed && before_deadline
+}
+
+/// Windows without a turnus or session, they apply to every session
+fn general(windows: &[RegistrationWindow]) -> Vec<&RegistrationWindow> {
+    windows
+        .iter()
+        .filter(|w| w.turnus.is_none() && w.session_id.is_none())
+        .collect()
+}
+
+/// Windows governing a session: its own if it has any, else its turnus', else the general ones
+fn governing<'a>(
+    windows: &'a [RegistrationWindow],
+    session: &Session,
+) -> Vec<&'a RegistrationWindow> {
+    let own: Vec<_> = windows
+        .iter()
+        .filter(|w| w.session_id == Some(session.id))
+        .collect();
+    if !own.is_empty() {
+        return own;
+    }
+
+    let turnus: Vec<_> = windows
+        .iter()
+        .filter(|w| w.session_id.is_none() && w.turnus == Some(session.turnus))
+        .collect();
+    if !turnus.is_empty() {
+        return turnus;
+    }
+
+    general(windows)
+}
+
+/// Whether a session takes bookings at `now`. Sessions without any window are always open.
+pub fn session_open(windows: &[RegistrationWindow], session: &Session, now: NaiveDateTime) -> bool {
+    let governing = governing(windows, session);
+    let start = session_start(session);
+    governing.is_empty() || governing.iter().any(|w| contains(w, now, Some(start)))
+}
+
+/// Registration status from the manual switch and the general windows
+pub fn status(
+    enabled: bool,
+    windows: &[RegistrationWindow],
+    now: NaiveDateTime,
+) -> RegistrationStatus {
+    let general = general(windows);
+    let current: Vec<_> = general.iter().filter(|w| contains(w, now, None)).collect();
+    let scheduled_open = general.is_empty() || !current.is_empty();
+    let open = enabled && scheduled_open;
+
+    // Latest end among the open windows, none when one of them never closes
+    let closes_at = if open {
+        current
+            .iter()
+            .map(|w| w.closes_at)
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ends| ends.into_iter().max())
+    } else {
+        None
+    };
+
+    let opens_at = if enabled && !scheduled_open {
+        general
+            .iter()
+            .filter_map(|w| w.opens_at)
+            .filter(|at| *at > now)
+            .min()
+    } else {
+        None
+    };
+
+    RegistrationStatus {
+        enabled,
+        open,
+        opens_at,
+        closes_at,
+    }
+}
+
+/// Why a session can't be booked at `now`, none when it can
+pub fn closed_reason(
+    windows: &[RegistrationWindow],
+    session: &Session,
+    now: NaiveDateTime,
+    cutoff_hours: i64,
+) -> Option<ClosedReason> {
+    let start = session_start(session);
+    if now >= start {
+        Some(ClosedReason::Past)
+    } else if now >= start - chrono::Duration::hours(cutoff_hours) {
+        Some(ClosedReason::Cutoff)
+    } else if !session_open(windows, session, now) {
+        Some(ClosedReason::Window)
+    } else {
+        None
+    }
+}
+
+/// Fail unless every given session takes bookings right now
+pub async fn ensure_open(
+    conn: &mut AsyncMysqlConnection,
+    state: &AppState,
+    config: &AppConfig,
+    session_ids: &[i32],
+) -> Result<(), ApiError> {
+    // The manual switch overrides every window
+    if !AtomicBool::load(&state.registration_enabled, Ordering::Relaxed) {
+        return Err(ApiError::registration_closed());
+    }
+
+    let windows = load(conn)
+        .await
+        .map_err(ApiError::internal("Error loading registration windows"))?;
+
+    // Unknown sessions are left to the booking, which answers them with 404
+    let sessions = sessions::table
+        .filter(sessions::id.eq_any(session_ids))
+        .load::<Session>(conn)
+        .await
+        .map_err(ApiError::internal("Error loading sessions"))?;
+
+    let now = state.clock.now();
+    for session in &sessions {
+        if let Some(reason) = closed_reason(&windows, session, now, config.booking_cutoff_hours) {
+            return Err(ApiError::from(reason).with_details(json!({ "session_id": session.id })));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::test_db;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn session() -> Session {
+        Session {
+            id: 1,
+            field_code: "EL".to_string(),
+            field_name: "Elektrotechnika".to_string(),
+            session_date: NaiveDate::from_ymd_opt(2026, 3, 10).unwrap(),
+            start_time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            max_capacity: 20,
+            turnus: 1,
+        }
+    }
+
+    fn window(opens_at: NaiveDateTime, closes_at: NaiveDateTime) -> RegistrationWindow {
+        RegistrationWindow {
+            id: 1,
+            turnus: None,
+            session_id: None,
+            opens_at: Some(opens_at),
+            closes_at: Some(closes_at),
+            closes_hours_before: None,
+        }
+    }
+
+    fn hours_before_start(hours: i64) -> NaiveDateTime {
+        session_start(&session()) - chrono::Duration::hours(hours)
+    }
+
+    #[test]
+    fn started_sessions_are_past() {
+        let session = session();
+        let start = session_start(&session);
+
+        assert_eq!(
+            closed_reason(&[], &session, start, 0),
+            Some(ClosedReason::Past)
+        );
+        assert_eq!(
+            closed_reason(&[], &session, start + chrono::Duration::days(1), 24),
+            Some(ClosedReason::Past)
+        );
+    }
+
+    #[test]
+    fn cutoff_closes_bookings_before_the_start() {
+        let session = session();
+
+        assert_eq!(
+            closed_reason(&[], &session, hours_before_start(24), 24),
+            Some(ClosedReason::Cutoff)
+        );
+        assert_eq!(
+            closed_reason(&[], &session, hours_before_start(2), 24),
+            Some(ClosedReason::Cutoff)
+        );
+        assert_eq!(
+            closed_reason(&[], &session, hours_before_start(25), 24),
+            None
+        );
+    }
+
+    #[test]
+    fn zero_cutoff_keeps_bookings_open_until_the_start() {
+        let session = session();
+        let just_before = session_start(&session) - chrono::Duration::seconds(1);
+
+        assert_eq!(closed_reason(&[], &session, just_before, 0), None);
+    }
+
+    #[test]
+    fn closed_windows_close_bookings() {
+        let session = session();
+        let windows = [window(hours_before_start(100), hours_before_start(50))];
+
+        assert_eq!(
+            closed_reason(&windows, &session, hours_before_start(120), 0),
+            Some(ClosedReason::Window)
+        );
+        assert_eq!(
+            closed_reason(&windows, &session, hours_before_start(40), 0),
+            Some(ClosedReason::Window)
+        );
+        assert_eq!(
+            closed_reason(&windows, &session, hours_before_start(60), 0),
+            None
+        );
+    }
+
+    #[test]
+    fn past_and_cutoff_take_precedence_over_windows() {
+        let session = session();
+        let windows = [window(hours_before_start(100), hours_before_start(50))];
+
+        assert_eq!(
+            closed_reason(&windows, &session, hours_before_start(10), 24),
+            Some(ClosedReason::Cutoff)
+        );
+        assert_eq!(
+            closed_reason(&windows, &session, hours_before_start(-1), 24),
+            Some(ClosedReason::Past)
+        );
+    }
+
+    #[test]
+    fn status_follows_a_fixed_clock() {
+        let windows = [window(hours_before_start(100), hours_before_start(50))];
+        let before = Clock::Fixed(hours_before_start(120));
+        let during = Clock::Fixed(hours_before_start(60));
+
+        let status_before = status(true, &windows, before.now());
+        assert!(!status_before.open);
+        assert_eq!(status_before.opens_at, Some(hours_before_start(100)));
+
+        let status_during = status(true, &windows, during.now());
+        assert!(status_during.open);
+        assert_eq!(status_during.closes_at, Some(hours_before_start(50)));
+    }
+
+    // Far future test sessions are open by the wall clock, a clock fixed past
+    // their start has to close them
+    #[rocket::async_test]
+    #[ignore = "needs a MySQL database in DATABASE_URL"]
+    async fn ensure_open_uses_the_state_clock() {
+        let mut conn = test_db::connect().await;
+        let session = test_db::insert_session(&mut conn, 1).await;
+        let mut config = AppConfig::load();
+        config.booking_cutoff_hours = 0;
+
+        let start = session_start(&session);
+        let open_state = test_db::app_state(Clock::Fixed(start - chrono::Duration::days(1)));
+        let past_state = test_db::app_state(Clock::Fixed(start + chrono::Duration::hours(1)));
+        let open = ensure_open(&mut conn, &open_state, &config, &[session.id]).await;
+        let past = ensure_open(&mut conn, &past_state, &config, &[session.id]).await;
+        test_db::delete_session(&mut conn, session.id).await;
+
+        assert!(open.is_ok(), "open: {:?}", open);
+        let past = past.expect_err("A started session must not take bookings");
+        assert!(
+            format!("{:?}", past).contains("\"session_past\""),
+            "past: {:?}",
+            past
+        );
+    }
+
+    #[test]
+    fn sessions_without_windows_are_open() {
+        assert_eq!(
+            closed_reason(&[], &session(), hours_before_start(48), 24),
+            None
+        );
+    }
+}
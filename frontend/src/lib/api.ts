@@ -8,6 +8,42 @@ export interface Session {
   max_capacity: number;
   turnus: number;
   available_spots: number;
+  waitlist_open: boolean;
+  registration_open: boolean;
+  /** Why the session takes no bookings, null when open or switched off as a whole */
+  closed_reason: "past" | "cutoff" | "window" | null;
+}
+
+export interface RegistrationStatus {
+  /** Manual switch of the admins */
+  enabled: boolean;
+  /** Registration is accepted right now */
+  open: boolean;
+  opens_at: string | null;
+  closes_at: string | null;
+}
+
+// Scheduled registration window, for all sessions, one turnus or one session
+export interface RegistrationWindowInput {
+  turnus: number | null;
+  session_id: number | null;
+  opens_at: string | null;
+  closes_at: string | null;
+  closes_hours_before: number | null;
+}
+
+export interface RegistrationWindow extends RegistrationWindowInput {
+  id: number;
+}
+
+export interface SessionInput {
+  field_code: string;
+  field_name: string;
+  session_date: string;
+  start_time: string;
+  end_time: string;
+  max_capacity: number;
+  turnus: number;
 }
 
 export interface CreateRegistrationRequest {
@@ -18,6 +54,31 @@ export interface CreateRegistrationRequest {
   guardian_last_name: string;
   guardian_phone: string;
   guardian_email: string;
+  join_waitlist?: boolean;
+  form_token: string;
+  website?: string;
+}
+
+// One guardian registering several students, booked all or none
+export interface CreateBatchRegistrationRequest {
+  guardian_first_name: string;
+  guardian_last_name: string;
+  guardian_phone: string;
+  guardian_email: string;
+  students: {
+    session_id: number;
+    student_first_name: string;
+    student_last_name: string;
+  }[];
+  join_waitlist?: boolean;
+  form_token: string;
+  website?: string;
+}
+
+export interface RegistrationCreated {
+  id: number;
+  manage_token: string;
+  waitlist_position: number | null;
 }
 
 export interface RegistrationResponse {
@@ -39,18 +100,183 @@ export interface RegistrationResponse {
   guardian_phone: string;
   guardian_email: string;
   confirmed: boolean;
+  waitlisted: boolean;
+  waitlist_position: number | null;
+  suspected_duplicate: boolean;
+  created_at: string;
+  /** When the student was checked in at the event */
+  checked_in_at: string | null;
+}
+
+export type LiveEvent =
+  | { type: "registration_status"; enabled: boolean }
+  | { type: "availability"; session_id: number; available_spots: number }
+  // Sent when events were missed, the listing has to be reloaded
+  | { type: "resync" };
+
+export type AdminRole = "viewer" | "manager";
+
+export interface AdminIdentity {
+  user_id: number;
+  username: string;
+  role: AdminRole;
+  totp_enabled: boolean;
+}
+
+export interface AdminLoginResponse {
+  totp_required: boolean;
+}
+
+export interface TotpSetup {
+  secret: string;
+  otpauth_url: string;
+  qr_svg: string;
+}
+
+export interface AdminUser {
+  id: number;
+  username: string;
+  role: AdminRole;
+  created_at: string | null;
+  totp_enabled: boolean;
+}
+
+export interface AdminLogin {
+  id: number;
+  created_at: string | null;
+  expires_at: string | null;
+  last_seen_at: string | null;
+  ip_address: string | null;
+  user_id: number;
+  username: string;
+  current: boolean;
+}
+
+// Rejections counted in memory since the server started at `since`
+export interface SpamStats {
+  since: string;
+  rate_limited: number;
+  honeypot: number;
+  invalid_token: number;
+  too_fast: number;
+}
+
+export interface AuditLogEntry {
+  id: number;
   created_at: string;
+  actor_id: number | null;
+  actor: string | null;
+  ip_address: string | null;
+  action: string;
+  target_id: string | null;
+  before: unknown;
+  after: unknown;
+}
+
+export interface AuditLogPage {
+  entries: AuditLogEntry[];
+  total: number;
+  page: number;
+  per_page: number;
+}
+
+// Diff of an uploaded session sheet, see `POST /api/admin/sessions/import`
+export interface SessionImportRow {
+  /** Row number in the uploaded sheet */
+  row: number;
+  action: "create" | "update" | "unchanged" | "invalid";
+  session_id: number | null;
+  session: SessionInput | null;
+  /** Fields that differ from the existing session */
+  changes: (keyof SessionInput)[];
+  /** Error code per invalid field */
+  errors: Record<string, string>;
+}
+
+export interface SessionImportReport {
+  dry_run: boolean;
+  rows: SessionImportRow[];
+  created: number;
+  updated: number;
+  unchanged: number;
+  invalid: number;
+  /** Existing sessions missing from the sheet, kept as they are */
+  untouched: Omit<Session, "available_spots" | "waitlist_open" | "registration_open" | "closed_reason">[];
+}
+
+export interface AuditLogFilter {
+  page?: number;
+  per_page?: number;
+  action?: string;
+  actor?: string;
+  target_id?: string;
 }
 
 export class ApiError extends Error {
   constructor(
     public status: number,
     message: string,
+    /** Machine readable code from the JSON error body, e.g. `session_full` */
+    public code?: string,
   ) {
     super(message);
   }
 }
 
+// JSON error body returned by every API route
+interface ApiErrorBody {
+  code?: string;
+  message?: string;
+  details?: {
+    fields?: Record<string, string>;
+    [key: string]: unknown;
+  };
+}
+
+async function readErrorBody(response: Response): Promise<ApiErrorBody> {
+  return (await response.json().catch(() => ({}))) as ApiErrorBody;
+}
+
+// Slovak labels and messages for the per-field codes of a 422 response
+const FIELD_LABELS: Record<string, string> = {
+  student_first_name: "Meno žiaka",
+  student_last_name: "Priezvisko žiaka",
+  guardian_first_name: "Meno zákonného zástupcu",
+  guardian_last_name: "Priezvisko zákonného zástupcu",
+  guardian_phone: "Telefón",
+  guardian_email: "Email",
+  students: "Zoznam žiakov",
+};
+
+const VALIDATION_MESSAGES: Record<string, string> = {
+  required: "je povinný údaj",
+  too_long: "je príliš dlhý",
+  invalid_email: "nie je platná emailová adresa",
+  invalid_phone: "nie je platné telefónne číslo (napr. 0905 123 456)",
+  too_many: "obsahuje príliš veľa položiek",
+};
+
+export class ValidationError extends ApiError {
+  constructor(
+    /** Slovak message per invalid field */
+    public fields: Record<string, string>,
+  ) {
+    super(422, Object.values(fields).join(". "));
+  }
+}
+
+async function validationError(response: Response): Promise<ValidationError> {
+  const body = await readErrorBody(response);
+  const fields: Record<string, string> = {};
+  for (const [field, code] of Object.entries(body.details?.fields ?? {})) {
+    // Batch student fields are keyed as students.<index>.<field>
+    const name = field.replace(/^students\.\d+\./, "");
+    const label = FIELD_LABELS[name] ?? name;
+    fields[field] = `${label} ${VALIDATION_MESSAGES[code] ?? "je neplatný"}`;
+  }
+  return new ValidationError(fields);
+}
+
 async function handleResponse<T>(response: Response): Promise<T> {
   if (!response.ok) {
     if (response.status === 412) {
@@ -60,12 +286,35 @@ async function handleResponse<T>(response: Response): Promise<T> {
       );
     }
     if (response.status === 409) {
-      throw new ApiError(response.status, "Tento termín je už plný");
+      const body = await readErrorBody(response);
+      if (body.code === "duplicate_registration") {
+        throw new ApiError(
+          response.status,
+          "Tento žiak je už prihlásený s rovnakým emailom zákonného zástupcu",
+          body.code,
+        );
+      }
+      if (body.code === "session_past" || body.code === "booking_cutoff") {
+        throw new ApiError(
+          response.status,
+          body.message ?? "Na tento termín sa už nie je možné prihlásiť",
+          body.code,
+        );
+      }
+      throw new ApiError(response.status, "Tento termín je už plný", body.code);
     }
     if (response.status === 404) {
       throw new ApiError(response.status, "Termín nebol nájdený");
     }
-    throw new ApiError(response.status, response.statusText);
+    if (response.status === 403) {
+      throw new ApiError(response.status, "Na túto akciu nemáte oprávnenie");
+    }
+    const body = await readErrorBody(response);
+    throw new ApiError(
+      response.status,
+      body.message ?? response.statusText,
+      body.code,
+    );
   }
   const text = await response.text();
   return text ? JSON.parse(text) : ({} as T);
@@ -77,30 +326,295 @@ export const api = {
     return handleResponse<Session[]>(res);
   },
 
-  async createRegistration(data: CreateRegistrationRequest): Promise<number> {
+  async getFormToken(): Promise<string> {
+    const res = await fetch("/api/form-token");
+    const data = await handleResponse<{ token: string }>(res);
+    return data.token;
+  },
+
+  async createRegistration(
+    data: CreateRegistrationRequest,
+  ): Promise<RegistrationCreated> {
     const res = await fetch("/api/register", {
       method: "POST",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify(data),
     });
-    return handleResponse<number>(res);
+    if (res.status === 429) {
+      throw new ApiError(
+        res.status,
+        "Príliš veľa prihlášok z vašej siete. Skúste to prosím neskôr.",
+      );
+    }
+    if (res.status === 422) {
+      throw await validationError(res);
+    }
+    if (res.status === 400) {
+      throw new ApiError(
+        res.status,
+        "Formulár vypršal alebo bol odoslaný príliš rýchlo. Obnovte stránku a skúste to znova.",
+      );
+    }
+    return handleResponse<RegistrationCreated>(res);
+  },
+
+  async createBatchRegistration(
+    data: CreateBatchRegistrationRequest,
+  ): Promise<RegistrationCreated[]> {
+    const res = await fetch("/api/register/batch", {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify(data),
+    });
+    if (res.status === 429) {
+      throw new ApiError(
+        res.status,
+        "Príliš veľa prihlášok z vašej siete. Skúste to prosím neskôr.",
+      );
+    }
+    if (res.status === 422) {
+      throw await validationError(res);
+    }
+    if (res.status === 400) {
+      throw new ApiError(
+        res.status,
+        "Formulár vypršal alebo bol odoslaný príliš rýchlo. Obnovte stránku a skúste to znova.",
+      );
+    }
+    const created = await handleResponse<{
+      registrations: RegistrationCreated[];
+    }>(res);
+    return created.registrations;
+  },
+
+  async getOwnRegistration(token: string): Promise<RegistrationResponse> {
+    const res = await fetch(`/api/registrations/${encodeURIComponent(token)}`);
+    if (res.status === 404) {
+      throw new ApiError(res.status, "Prihláška nebola nájdená");
+    }
+    return handleResponse<RegistrationResponse>(res);
+  },
+
+  async cancelOwnRegistration(token: string): Promise<void> {
+    const res = await fetch(`/api/registrations/${encodeURIComponent(token)}`, {
+      method: "DELETE",
+    });
+    if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa zrušiť prihlášku");
   },
 
-  async getRegistrationStatus(): Promise<boolean> {
+  async moveOwnRegistration(token: string, sessionId: number): Promise<void> {
+    const res = await fetch(
+      `/api/registrations/${encodeURIComponent(token)}/move`,
+      {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ session_id: sessionId }),
+      },
+    );
+    return handleResponse<void>(res);
+  },
+
+  // QR code the student shows at the entrance, served once the booking is confirmed
+  checkinQrUrl(token: string): string {
+    return `/api/registrations/${encodeURIComponent(token)}/checkin.svg`;
+  },
+
+  async getRegistrationStatus(): Promise<RegistrationStatus> {
     const res = await fetch("/api/status");
-    return handleResponse<boolean>(res);
+    return handleResponse<RegistrationStatus>(res);
+  },
+
+  // Open the live update socket, reconnecting after the connection drops
+  subscribeLive(onEvent: (event: LiveEvent) => void): void {
+    const protocol = window.location.protocol === "https:" ? "wss" : "ws";
+    const socket = new WebSocket(`${protocol}://${window.location.host}/api/ws`);
+    socket.addEventListener("message", (message) => {
+      try {
+        onEvent(JSON.parse(message.data) as LiveEvent);
+      } catch (error) {
+        console.error("Invalid live event:", error);
+      }
+    });
+    socket.addEventListener("close", () => {
+      setTimeout(() => api.subscribeLive(onEvent), 5000);
+    });
   },
 
   admin: {
-    async login(password: string): Promise<void> {
+    async login(username: string, password: string): Promise<AdminLoginResponse> {
       const res = await fetch("/api/admin/login", {
         method: "POST",
         headers: { "Content-Type": "application/json" },
-        body: JSON.stringify({ password }),
+        body: JSON.stringify({ username, password }),
+      });
+      if (res.status === 429) {
+        const seconds = Number(res.headers.get("Retry-After")) || 60;
+        const minutes = Math.ceil(seconds / 60);
+        throw new ApiError(
+          res.status,
+          `Príliš veľa neúspešných pokusov. Skúste to znova o ${minutes} min.`,
+        );
+      }
+      if (!res.ok) {
+        throw new ApiError(res.status, "Nesprávne meno alebo heslo");
+      }
+      return res.json();
+    },
+
+    async loginTotp(code: string): Promise<void> {
+      const res = await fetch("/api/admin/login/totp", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ code }),
       });
+      if (res.status === 429) {
+        const seconds = Number(res.headers.get("Retry-After")) || 60;
+        const minutes = Math.ceil(seconds / 60);
+        throw new ApiError(
+          res.status,
+          `Príliš veľa neúspešných pokusov. Skúste to znova o ${minutes} min.`,
+        );
+      }
       if (!res.ok) {
-        throw new ApiError(res.status, "Nesprávne heslo");
+        throw new ApiError(res.status, "Nesprávny overovací kód");
+      }
+    },
+
+    async setupTotp(): Promise<TotpSetup> {
+      const res = await fetch("/api/admin/totp/setup", { method: "POST" });
+      return handleResponse<TotpSetup>(res);
+    },
+
+    async enableTotp(code: string): Promise<string[]> {
+      const res = await fetch("/api/admin/totp/enable", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ code }),
+      });
+      if (res.status === 422) {
+        throw new ApiError(res.status, "Nesprávny overovací kód");
+      }
+      const data = await handleResponse<{ recovery_codes: string[] }>(res);
+      return data.recovery_codes;
+    },
+
+    async disableTotp(code: string): Promise<void> {
+      const res = await fetch("/api/admin/totp/disable", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ code }),
+      });
+      if (res.status === 422) {
+        throw new ApiError(res.status, "Nesprávny overovací kód");
+      }
+      await handleResponse<void>(res);
+    },
+
+    async resetUserTotp(id: number): Promise<void> {
+      const res = await fetch(`/api/admin/users/${id}/totp`, {
+        method: "DELETE",
+      });
+      await handleResponse<void>(res);
+    },
+
+    async me(): Promise<AdminIdentity> {
+      const res = await fetch("/api/admin/me");
+      return handleResponse<AdminIdentity>(res);
+    },
+
+    async getUsers(): Promise<AdminUser[]> {
+      const res = await fetch("/api/admin/users");
+      return handleResponse<AdminUser[]>(res);
+    },
+
+    async createUser(
+      username: string,
+      password: string,
+      role: AdminRole,
+    ): Promise<void> {
+      const res = await fetch("/api/admin/users", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ username, password, role }),
+      });
+      if (res.status === 409) {
+        throw new ApiError(res.status, "Používateľ s týmto menom už existuje");
       }
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa vytvoriť používateľa");
+    },
+
+    async deleteUser(id: number): Promise<void> {
+      const res = await fetch(`/api/admin/users/${id}`, {
+        method: "DELETE",
+      });
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa zmazať používateľa");
+    },
+
+    async getLogins(): Promise<AdminLogin[]> {
+      const res = await fetch("/api/admin/logins");
+      return handleResponse<AdminLogin[]>(res);
+    },
+
+    async revokeLogin(id: number): Promise<void> {
+      const res = await fetch(`/api/admin/logins/${id}`, {
+        method: "DELETE",
+      });
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa zrušiť prihlásenie");
+    },
+
+    async getWindows(): Promise<RegistrationWindow[]> {
+      const res = await fetch("/api/admin/windows");
+      return handleResponse<RegistrationWindow[]>(res);
+    },
+
+    async createWindow(
+      window: RegistrationWindowInput,
+    ): Promise<RegistrationWindow> {
+      const res = await fetch("/api/admin/windows", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(window),
+      });
+      if (res.status === 422) {
+        throw new ApiError(res.status, "Skontrolujte zadané časy okna");
+      }
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa vytvoriť okno");
+      return res.json();
+    },
+
+    async updateWindow(
+      id: number,
+      window: RegistrationWindowInput,
+    ): Promise<RegistrationWindow> {
+      const res = await fetch(`/api/admin/windows/${id}`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(window),
+      });
+      if (res.status === 422) {
+        throw new ApiError(res.status, "Skontrolujte zadané časy okna");
+      }
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa upraviť okno");
+      return res.json();
+    },
+
+    async deleteWindow(id: number): Promise<void> {
+      const res = await fetch(`/api/admin/windows/${id}`, { method: "DELETE" });
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa zmazať okno");
+    },
+
+    async getSpamStats(): Promise<SpamStats> {
+      const res = await fetch("/api/admin/spam-stats");
+      return handleResponse<SpamStats>(res);
+    },
+
+    async getAuditLog(filter: AuditLogFilter = {}): Promise<AuditLogPage> {
+      const params = new URLSearchParams();
+      for (const [key, value] of Object.entries(filter)) {
+        if (value !== undefined && value !== "") params.set(key, String(value));
+      }
+      const res = await fetch(`/api/admin/audit?${params}`);
+      return handleResponse<AuditLogPage>(res);
     },
 
     async logout(): Promise<void> {
@@ -124,9 +638,30 @@ export const api = {
       const res = await fetch(`/api/admin/registrations/${id}/confirm`, {
         method: "POST",
       });
+      if (res.status === 409) {
+        throw new ApiError(res.status, "Termín je už plný, registráciu nie je možné potvrdiť");
+      }
       if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa potvrdiť registráciu");
     },
 
+    // Record that the student holding the scanned QR code arrived
+    async checkIn(code: string): Promise<RegistrationResponse> {
+      const res = await fetch("/api/admin/checkin", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ code }),
+      });
+      if (res.status === 404 || res.status === 409) {
+        const body = await readErrorBody(res);
+        throw new ApiError(
+          res.status,
+          body.message ?? "Kód nie je platný",
+          body.code,
+        );
+      }
+      return handleResponse<RegistrationResponse>(res);
+    },
+
     async deleteRegistration(id: number): Promise<void> {
       const res = await fetch(`/api/admin/registrations/${id}`, {
         method: "DELETE",
@@ -150,6 +685,64 @@ export const api = {
       document.body.removeChild(a);
     },
 
+    async createSession(data: SessionInput): Promise<void> {
+      const res = await fetch("/api/admin/sessions", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(data),
+      });
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa vytvoriť termín");
+    },
+
+    async updateSession(id: number, data: SessionInput): Promise<void> {
+      const res = await fetch(`/api/admin/sessions/${id}`, {
+        method: "PUT",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify(data),
+      });
+      if (res.status === 409) {
+        throw new ApiError(res.status, "Kapacita nemôže byť nižšia ako počet obsadených miest");
+      }
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa upraviť termín");
+    },
+
+    async deleteSession(id: number, cascade = false): Promise<void> {
+      const res = await fetch(`/api/admin/sessions/${id}?cascade=${cascade}`, {
+        method: "DELETE",
+      });
+      if (res.status === 409) {
+        throw new ApiError(res.status, "Termín má prihlášky, nie je možné ho zmazať");
+      }
+      if (!res.ok) throw new ApiError(res.status, "Nepodarilo sa zmazať termín");
+    },
+
+    // Check an .xlsx or CSV sheet of sessions, or write it when dryRun is false.
+    // A sheet with invalid rows comes back as a report, nothing is written then.
+    async importSessions(
+      file: File,
+      dryRun: boolean,
+    ): Promise<SessionImportReport> {
+      const res = await fetch(`/api/admin/sessions/import?dry_run=${dryRun}`, {
+        method: "POST",
+        body: file,
+      });
+      if (res.status === 422) {
+        const body = await readErrorBody(res);
+        if (body.code === "import_invalid") {
+          return body.details as unknown as SessionImportReport;
+        }
+        throw new ApiError(
+          res.status,
+          body.message ?? "Súbor sa nepodarilo spracovať",
+          body.code,
+        );
+      }
+      if (res.status === 413) {
+        throw new ApiError(res.status, "Súbor je príliš veľký");
+      }
+      return handleResponse<SessionImportReport>(res);
+    },
+
     async toggleRegistration(): Promise<boolean> {
       const res = await fetch("/api/admin/toggle", {
         method: "POST",